@@ -0,0 +1,23 @@
+//! Parses a file and prints a miette report on failure:
+//!     cargo run --features miette --example miette_report -- <file>
+
+use std::path::PathBuf;
+
+fn main() -> miette::Result<()> {
+    let path = std::env::args()
+        .nth(1)
+        .expect("usage: miette_report <file>");
+    let file = yapl::File::new_read(PathBuf::from(path)).map_err(miette::Report::msg)?;
+    match yapl::parse(&file) {
+        Ok(_) => {
+            println!("ok");
+            Ok(())
+        }
+        Err(errors) => {
+            for error in &errors {
+                println!("{:?}", miette::Report::new(yapl::Diagnostic::new(error, &file)))
+            }
+            Err(miette::Report::msg("parsing failed"))
+        }
+    }
+}
@@ -1,23 +1,39 @@
-use crate::common::error::Result;
-use crate::common::location::Span;
+use std::path::{Path, PathBuf};
+
+use crate::common::error::{Error, Result};
+use crate::common::location::{implement_has_span, FileId, Span};
 use crate::common::symbol::Symbol;
 use crate::common::Id as Obj;
 
 use super::context::ContextPart;
 
+use serde::{Deserialize, Serialize};
+
 // pub type Id = crate::common::space::Id;
 // pub type Space = crate::common::space::Space<Value>;
 
+/// `PartialEq` compares spans too; `Node::LitFloat` keeps `Eq`
+///     and `Hash` off the tree - use `structural_eq` to compare
+///     regardless of source positions.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct Project {
     // space: Space,
-    // files: HashMap<PathBuf, File>,
     roots: Vec<Line>,
+    /// Paths of the files added via `add_file`, indexed by
+    ///     `FileId`. Empty for single-file projects built
+    ///     with `new` - those keep the default id 0.
+    #[serde(default)]
+    files: Vec<PathBuf>,
 }
 
-#[derive(Debug, Clone, derive_new::new)]
+#[derive(Debug, Clone, PartialEq, derive_new::new, getset::Getters, getset::MutGetters)]
+#[derive(Serialize, Deserialize)]
 pub struct Line {
+    #[getset(get = "pub", get_mut = "pub")]
     line: NodeS,
+    #[getset(get = "pub", get_mut = "pub")]
     extension: Vec<Line>,
+    #[getset(get = "pub", get_mut = "pub")]
     block: Vec<Line>,
     // #[new(default)]
     // context: Context<Object>,
@@ -35,8 +51,10 @@ impl PartialEq for Value {
     }
 }
 
-#[derive(Debug, Clone, derive_new::new)]
+#[derive(Debug, Clone, PartialEq, derive_new::new, getset::Getters, getset::MutGetters)]
+#[derive(Serialize, Deserialize)]
 pub struct NodeS {
+    #[getset(get = "pub", get_mut = "pub")]
     node: Node,
     span: Span,
 }
@@ -47,22 +65,48 @@ pub enum Action {
     None,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Node {
     Phrase(Vec<NodeS>),
     Bracket(Bracket, Vec<NodeS>),
     Chain(Vec<Symbol>),
+    Keyword(Symbol),
+    /// `callee(args)`: a chain or keyword fused with the bracket
+    ///     immediately following it, one element per comma part.
+    Call(Box<NodeS>, Vec<NodeS>),
+    /// `base[index]`: like `Call`, with square brackets.
+    Index(Box<NodeS>, Vec<NodeS>),
+    /// `@name` or `@name(args)`: the `@` fused with the chain
+    ///     touching it, plus the call arguments when a bracket is
+    ///     glued on. Annotates what follows - the rest of its line
+    ///     or, standalone, the line's indented block.
+    Annotation(Box<NodeS>, Vec<NodeS>),
+    /// `(a)`: a grouped expression - produced by `lower_brackets`,
+    ///     never by `parser2ast` itself.
+    Group(Box<NodeS>),
+    /// `(a, b)` or `()`, one element per comma part.
+    Tuple(Vec<NodeS>),
+    /// `[...]`, one element per comma part.
+    List(Vec<NodeS>),
+    /// `{...}`, one element per comma part. Blocks come from
+    ///     indentation, so inline `{}` is always a collection.
+    Set(Vec<NodeS>),
     LitStr(String),
     LitInt(i64),
+    LitFloat(f64),
+    LitChar(char),
+    LitBool(bool),
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Bracket {
     Round,
     Square,
     Curly,
 }
 
+implement_has_span!(Line, NodeS);
+
 // To be done: this should be primary way to access ast.
 // To be done: this should be able to modify ast.
 pub struct Access<'project> {
@@ -72,7 +116,94 @@ pub struct Access<'project> {
 impl Project {
     pub fn new(roots: Vec<Line>) -> Self {
         // let space = Space::default();
-        Self { /* space,*/ roots, }
+        Self {
+            /* space,*/ roots,
+            files: Vec::new(),
+        }
+    }
+
+    /// Appends the lines of another file; every span of those
+    ///     lines is stamped with the returned handle.
+    pub fn add_file(&mut self, path: PathBuf, mut roots: Vec<Line>) -> FileId {
+        let id = FileId::new(self.files.len());
+        self.files.push(path);
+        for line in &mut roots {
+            line.set_file(id)
+        }
+        self.roots.append(&mut roots);
+        id
+    }
+
+    pub fn file_path(&self, id: FileId) -> Option<&Path> {
+        self.files.get(id.as_usize()).map(|p| p.as_path())
+    }
+
+    /// The root lines contributed by file `id`.
+    pub fn file_roots(&self, id: FileId) -> impl Iterator<Item = &Line> {
+        self.roots.iter().filter(move |line| line.span.file() == id)
+    }
+
+    /// Like parsing `file` and `add_file`-ing the result, except
+    ///     the converted lines are memoized in `cache` keyed by a
+    ///     hash of the contents - an unchanged file skips the
+    ///     parse entirely, even if it moved or was renamed.
+    pub fn parse_cached(
+        &mut self,
+        file: &crate::common::location::File,
+        cache: &mut dyn ParseCache,
+    ) -> std::result::Result<FileId, Vec<Error>> {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        file.code().hash(&mut hasher);
+        let hash = hasher.finish();
+        let roots = match cache.get(hash) {
+            Some(roots) => roots,
+            None => {
+                let parsed = crate::parser::parse(file)?;
+                let roots =
+                    crate::glue::parser2ast::parser2ast(&parsed).map_err(|e| vec![e])?;
+                cache.put(hash, roots.clone());
+                roots
+            }
+        };
+        Ok(self.add_file(file.get_path().to_path_buf(), roots))
+    }
+
+    /// Parses every file under `dir` (recursively) whose extension
+    ///     is `extension` into one project. A failing file doesn't
+    ///     stop the others - its errors come back keyed by path.
+    /// `Err` only when the directory itself cannot be read.
+    /// With the `rayon` feature files parse in parallel; every
+    ///     file is independent, so the speedup is close to the
+    ///     core count, bounded by the shared symbol interner lock.
+    ///     Paths are sorted up front and results collected in that
+    ///     order, so the output never depends on scheduling.
+    pub fn from_dir(
+        dir: &Path,
+        extension: &str,
+    ) -> std::result::Result<(Self, Vec<(PathBuf, Vec<Error>)>), String> {
+        let mut paths = Vec::new();
+        discover(dir, extension, &mut paths)?;
+        // Deterministic file ids however the OS orders entries.
+        paths.sort();
+        let mut project = Self::new(Vec::new());
+        let mut errors = Vec::new();
+        for (path, result) in parse_all(paths) {
+            match result {
+                Ok(roots) => {
+                    project.add_file(path, roots);
+                }
+                Err(e) => errors.push((path, e)),
+            }
+        }
+        Ok((project, errors))
+    }
+
+    pub fn roots(&self) -> &Vec<Line> {
+        &self.roots
+    }
+    pub fn roots_mut(&mut self) -> &mut Vec<Line> {
+        &mut self.roots
     }
 
     pub fn interpret(self) -> Result<Vec<Obj>> {
@@ -87,7 +218,90 @@ impl Project {
     }
 }
 
+fn discover(dir: &Path, extension: &str, paths: &mut Vec<PathBuf>) -> std::result::Result<(), String> {
+    for entry in std::fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.is_dir() {
+            discover(&path, extension, paths)?
+        } else if path.extension().and_then(|e| e.to_str()) == Some(extension) {
+            paths.push(path)
+        }
+    }
+    Ok(())
+}
+
+/// Storage behind `Project::parse_cached`. The key is a content
+///     hash - never a path or mtime. Implement it over disk to
+///     keep results across runs.
+pub trait ParseCache {
+    fn get(&self, hash: u64) -> Option<Vec<Line>>;
+    fn put(&mut self, hash: u64, roots: Vec<Line>);
+}
+
+/// The obvious in-process `ParseCache`.
+#[derive(Default)]
+pub struct MemoryCache {
+    entries: std::collections::HashMap<u64, Vec<Line>>,
+}
+
+impl ParseCache for MemoryCache {
+    fn get(&self, hash: u64) -> Option<Vec<Line>> {
+        self.entries.get(&hash).cloned()
+    }
+
+    fn put(&mut self, hash: u64, roots: Vec<Line>) {
+        self.entries.insert(hash, roots);
+    }
+}
+
+type ParsedFile = (PathBuf, std::result::Result<Vec<Line>, Vec<Error>>);
+
+// `collect` keeps input order for both iterators, serial and
+//     parallel alike.
+#[cfg(feature = "rayon")]
+fn parse_all(paths: Vec<PathBuf>) -> Vec<ParsedFile> {
+    use rayon::prelude::*;
+    paths
+        .into_par_iter()
+        .map(|path| {
+            let result = parse_one(&path);
+            (path, result)
+        })
+        .collect()
+}
+
+#[cfg(not(feature = "rayon"))]
+fn parse_all(paths: Vec<PathBuf>) -> Vec<ParsedFile> {
+    paths
+        .into_iter()
+        .map(|path| {
+            let result = parse_one(&path);
+            (path, result)
+        })
+        .collect()
+}
+
+fn parse_one(path: &Path) -> std::result::Result<Vec<Line>, Vec<Error>> {
+    let file = match crate::common::location::File::new_read(path.to_path_buf()) {
+        Ok(file) => file,
+        Err(reason) => {
+            let error = crate::parser::errors::ReadFailed::new(Default::default(), reason);
+            return Err(vec![Box::new(error)]);
+        }
+    };
+    let parsed = crate::parser::parse(&file)?;
+    crate::glue::parser2ast::parser2ast(&parsed).map_err(|e| vec![e])
+}
+
 impl Line {
+    fn set_file(&mut self, file: FileId) {
+        self.span = self.span.in_file(file);
+        self.line.set_file(file);
+        for line in self.extension.iter_mut().chain(self.block.iter_mut()) {
+            line.set_file(file)
+        }
+    }
+
     fn act(
         self,
         /* space: &mut Space, */
@@ -104,6 +318,29 @@ impl Line {
 }
 
 impl NodeS {
+    fn set_file(&mut self, file: FileId) {
+        self.span = self.span.in_file(file);
+        match &mut self.node {
+            Node::Phrase(sub)
+            | Node::Bracket(_, sub)
+            | Node::Tuple(sub)
+            | Node::List(sub)
+            | Node::Set(sub) => {
+                for item in sub {
+                    item.set_file(file)
+                }
+            }
+            Node::Call(callee, args) | Node::Index(callee, args) | Node::Annotation(callee, args) => {
+                callee.set_file(file);
+                for item in args {
+                    item.set_file(file)
+                }
+            }
+            Node::Group(inner) => inner.set_file(file),
+            _ => {}
+        }
+    }
+
     fn process_sub_lines(
         &self,
         _extension: &mut Vec<Line>,
@@ -114,6 +351,90 @@ impl NodeS {
     }
 }
 
+/// `let project: Project = src.parse()?` - the most ergonomic
+///     entry point: an in-memory `File` under a placeholder name
+///     through the full parse + `parser2ast` pipeline.
+impl std::str::FromStr for Project {
+    type Err = Vec<Error>;
+
+    fn from_str(src: &str) -> std::result::Result<Self, Self::Err> {
+        let file = match crate::common::location::File::new_str("<string>".into(), src) {
+            Ok(file) => file,
+            Err(reason) => {
+                let error = crate::parser::errors::ReadFailed::new(Default::default(), reason);
+                return Err(vec![Box::new(error)]);
+            }
+        };
+        let parsed = crate::parser::parse(&file)?;
+        let roots = crate::glue::parser2ast::parser2ast(&parsed).map_err(|e| vec![e])?;
+        Ok(Self::new(roots))
+    }
+}
+
+impl Project {
+    /// Span-insensitive comparison - see `Line::structural_eq`.
+    pub fn structural_eq(&self, other: &Self) -> bool {
+        self.roots.len() == other.roots.len()
+            && self
+                .roots
+                .iter()
+                .zip(&other.roots)
+                .all(|(a, b)| a.structural_eq(b))
+    }
+}
+
+impl Line {
+    /// Comparison ignoring spans, so structurally identical trees
+    ///     from different source positions compare equal - for
+    ///     tests and subtree deduplication. `PartialEq` compares
+    ///     spans too.
+    pub fn structural_eq(&self, other: &Self) -> bool {
+        let all = |a: &[Line], b: &[Line]| {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.structural_eq(b))
+        };
+        self.line.structural_eq(&other.line)
+            && all(&self.extension, &other.extension)
+            && all(&self.block, &other.block)
+    }
+}
+
+impl NodeS {
+    /// Span-insensitive comparison - see `Line::structural_eq`.
+    pub fn structural_eq(&self, other: &Self) -> bool {
+        self.node.structural_eq(&other.node)
+    }
+}
+
+impl Node {
+    /// Span-insensitive comparison - see `Line::structural_eq`.
+    pub fn structural_eq(&self, other: &Self) -> bool {
+        let all = |a: &[NodeS], b: &[NodeS]| {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.structural_eq(b))
+        };
+        match (self, other) {
+            (Self::Phrase(a), Self::Phrase(b)) => all(a, b),
+            (Self::Bracket(a_bt, a), Self::Bracket(b_bt, b)) => a_bt == b_bt && all(a, b),
+            (Self::Chain(a), Self::Chain(b)) => a == b,
+            (Self::Keyword(a), Self::Keyword(b)) => a == b,
+            (Self::Call(a_head, a), Self::Call(b_head, b))
+            | (Self::Index(a_head, a), Self::Index(b_head, b))
+            | (Self::Annotation(a_head, a), Self::Annotation(b_head, b)) => {
+                a_head.structural_eq(b_head) && all(a, b)
+            }
+            (Self::Group(a), Self::Group(b)) => a.structural_eq(b),
+            (Self::Tuple(a), Self::Tuple(b))
+            | (Self::List(a), Self::List(b))
+            | (Self::Set(a), Self::Set(b)) => all(a, b),
+            (Self::LitStr(a), Self::LitStr(b)) => a == b,
+            (Self::LitInt(a), Self::LitInt(b)) => a == b,
+            (Self::LitFloat(a), Self::LitFloat(b)) => a == b,
+            (Self::LitChar(a), Self::LitChar(b)) => a == b,
+            (Self::LitBool(a), Self::LitBool(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
 // To be done: macro.
 impl NodeS {
     pub fn new_p(phrase: Vec<NodeS>, span: Span) -> Self {
@@ -124,6 +445,22 @@ impl NodeS {
         let node = Node::Chain(chain);
         Self { node, span }
     }
+    pub fn new_k(keyword: Symbol, span: Span) -> Self {
+        let node = Node::Keyword(keyword);
+        Self { node, span }
+    }
+    pub fn new_call(callee: NodeS, args: Vec<NodeS>, span: Span) -> Self {
+        let node = Node::Call(Box::new(callee), args);
+        Self { node, span }
+    }
+    pub fn new_index(base: NodeS, index: Vec<NodeS>, span: Span) -> Self {
+        let node = Node::Index(Box::new(base), index);
+        Self { node, span }
+    }
+    pub fn new_annotation(name: NodeS, args: Vec<NodeS>, span: Span) -> Self {
+        let node = Node::Annotation(Box::new(name), args);
+        Self { node, span }
+    }
     pub fn new_br(inner: Vec<NodeS>, span: Span) -> Self {
         let node = Node::Bracket(Bracket::Round, inner);
         Self { node, span }
@@ -144,4 +481,183 @@ impl NodeS {
         let node = Node::LitInt(lit_int);
         Self { node, span }
     }
+    pub fn new_lf(lit_float: f64, span: Span) -> Self {
+        let node = Node::LitFloat(lit_float);
+        Self { node, span }
+    }
+    pub fn new_lc(lit_char: char, span: Span) -> Self {
+        let node = Node::LitChar(lit_char);
+        Self { node, span }
+    }
+    pub fn new_lb(lit_bool: bool, span: Span) -> Self {
+        let node = Node::LitBool(lit_bool);
+        Self { node, span }
+    }
+    pub fn new_group(inner: NodeS, span: Span) -> Self {
+        let node = Node::Group(Box::new(inner));
+        Self { node, span }
+    }
+    pub fn new_tuple(parts: Vec<NodeS>, span: Span) -> Self {
+        let node = Node::Tuple(parts);
+        Self { node, span }
+    }
+    pub fn new_list(parts: Vec<NodeS>, span: Span) -> Self {
+        let node = Node::List(parts);
+        Self { node, span }
+    }
+    pub fn new_set(parts: Vec<NodeS>, span: Span) -> Self {
+        let node = Node::Set(parts);
+        Self { node, span }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::location::{HasSpan, Position};
+
+    fn span(begin: usize, end: usize) -> Span {
+        Span::new(Position::new(begin).unwrap(), Position::new(end).unwrap())
+    }
+
+    fn line(name: &'static str, at: Span) -> Line {
+        let head = NodeS::new_c(vec![name.into()], at);
+        Line::new(NodeS::new_p(vec![head], at), Vec::new(), Vec::new(), at)
+    }
+
+    #[test]
+    fn file_ids() {
+        let mut project = Project::new(vec![line("x", span(0, 1))]);
+        let first = project.add_file("a.yapl".into(), vec![line("y", span(0, 1))]);
+        let second = project.add_file("b.yapl".into(), vec![line("z", span(0, 1))]);
+        assert_ne!(first, second);
+        assert_eq!(project.file_path(first).unwrap().to_str(), Some("a.yapl"));
+        // Lines built without `add_file` keep the default id.
+        assert_eq!(project.roots()[0].span().file(), FileId::default());
+        assert_eq!(project.roots()[1].span().file(), first);
+        assert_eq!(project.roots()[1].line().span().file(), first);
+        assert_eq!(project.roots()[2].span().file(), second);
+        // Callee and argument spans are stamped too.
+        let call = NodeS::new_call(
+            NodeS::new_c(vec!["f".into()], span(0, 1)),
+            vec![NodeS::new_li(1, span(2, 3))],
+            span(0, 4),
+        );
+        let root = Line::new(NodeS::new_p(vec![call], span(0, 4)), Vec::new(), Vec::new(), span(0, 4));
+        let third = project.add_file("c.yapl".into(), vec![root]);
+        let phrase = match project.roots()[3].line().node() {
+            Node::Phrase(phrase) => phrase,
+            other => panic!("not a phrase: {:?}", other),
+        };
+        match phrase[0].node() {
+            Node::Call(callee, args) => {
+                assert_eq!(callee.span().file(), third);
+                assert_eq!(args[0].span().file(), third);
+            }
+            other => panic!("not a call: {:?}", other),
+        }
+        // The `lower_brackets` variants are stamped too.
+        let list = NodeS::new_list(vec![NodeS::new_li(1, span(1, 2))], span(0, 3));
+        let root = Line::new(NodeS::new_p(vec![list], span(0, 3)), Vec::new(), Vec::new(), span(0, 3));
+        let fourth = project.add_file("d.yapl".into(), vec![root]);
+        let phrase = match project.roots()[4].line().node() {
+            Node::Phrase(phrase) => phrase,
+            other => panic!("not a phrase: {:?}", other),
+        };
+        match phrase[0].node() {
+            Node::List(parts) => assert_eq!(parts[0].span().file(), fourth),
+            other => panic!("not a list: {:?}", other),
+        }
+        // Annotation name and argument spans as well.
+        let name = NodeS::new_c(vec!["test".into()], span(1, 5));
+        let annotation = NodeS::new_annotation(name, vec![NodeS::new_li(1, span(6, 7))], span(0, 8));
+        let root = Line::new(NodeS::new_p(vec![annotation], span(0, 8)), Vec::new(), Vec::new(), span(0, 8));
+        let fifth = project.add_file("e.yapl".into(), vec![root]);
+        let phrase = match project.roots()[5].line().node() {
+            Node::Phrase(phrase) => phrase,
+            other => panic!("not a phrase: {:?}", other),
+        };
+        match phrase[0].node() {
+            Node::Annotation(name, args) => {
+                assert_eq!(name.span().file(), fifth);
+                assert_eq!(args[0].span().file(), fifth);
+            }
+            other => panic!("not an annotation: {:?}", other),
+        }
+    }
+
+    #[derive(Default)]
+    struct CountingCache {
+        inner: MemoryCache,
+        misses: usize,
+    }
+
+    impl ParseCache for CountingCache {
+        fn get(&self, hash: u64) -> Option<Vec<Line>> {
+            self.inner.get(hash)
+        }
+
+        fn put(&mut self, hash: u64, roots: Vec<Line>) {
+            self.misses += 1;
+            self.inner.put(hash, roots)
+        }
+    }
+
+    #[test]
+    fn cache_hits_by_content() {
+        let mut project = Project::new(Vec::new());
+        let mut cache = CountingCache::default();
+        let file = |path: &str| crate::common::location::File::new_str(path.into(), "f x\n").unwrap();
+        let a = project.parse_cached(&file("a.yapl"), &mut cache).unwrap();
+        // Same content under another name: no second parse.
+        let b = project.parse_cached(&file("b.yapl"), &mut cache).unwrap();
+        assert_eq!(cache.misses, 1);
+        assert_ne!(a, b);
+        assert_eq!(project.roots().len(), 2);
+        let other = crate::common::location::File::new_str("c.yapl".into(), "g y\n").unwrap();
+        project.parse_cached(&other, &mut cache).unwrap();
+        assert_eq!(cache.misses, 2);
+    }
+
+    #[test]
+    fn project_from_str() {
+        let project: Project = "f x\n  g y\n".parse().unwrap();
+        assert_eq!(project.roots().len(), 1);
+        assert_eq!(project.roots()[0].block().len(), 1);
+        assert!("f (\n".parse::<Project>().is_err());
+    }
+
+    #[test]
+    fn structural_equality() {
+        let a = NodeS::new_c(vec!["x".into()], span(0, 1));
+        let b = NodeS::new_c(vec!["x".into()], span(4, 5));
+        // Spans differ, the structure doesn't.
+        assert!(a != b);
+        assert!(a.structural_eq(&b));
+        assert!(!a.structural_eq(&NodeS::new_k("x".into(), span(0, 1))));
+        // Sub nodes are compared structurally too.
+        let call = |head: &NodeS| NodeS::new_call(head.clone(), vec![a.clone()], span(0, 3));
+        assert!(call(&a).structural_eq(&call(&b)));
+    }
+
+    #[test]
+    fn from_dir_collects_per_file() {
+        let dir = std::env::temp_dir().join(format!("yapl-from-dir-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("a.yapl"), "f x\n").unwrap();
+        std::fs::write(dir.join("sub/b.yapl"), "g y\n").unwrap();
+        std::fs::write(dir.join("bad.yapl"), "f\n    g\n").unwrap();
+        std::fs::write(dir.join("skip.txt"), "not source").unwrap();
+
+        let (project, errors) = Project::from_dir(&dir, "yapl").unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // The broken file reports its errors, the others parse.
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].0.ends_with("bad.yapl"));
+        assert_eq!(project.roots().len(), 2);
+        let first = project.roots()[0].span().file();
+        assert!(project.file_path(first).unwrap().ends_with("a.yapl"));
+        assert_eq!(project.file_roots(first).count(), 1);
+    }
 }
@@ -0,0 +1,9 @@
+use crate::common::error::error_struct;
+use crate::common::location::Span;
+
+error_struct!(DuplicateDefinition, "`{}` is already defined at {:?}", name: String, previous: Span);
+error_struct!(MissingOperator, "expected an operator between operands",);
+error_struct!(TrailingOperator, "operator without a right operand",);
+error_struct!(UnknownOperator, "operator `{}` isn't in the precedence table", operator: String);
+error_struct!(DivisionByZero, "division by zero in a constant expression",);
+error_struct!(ArithmeticOverflow, "constant expression overflows its type",);
@@ -0,0 +1,295 @@
+use crate::common::error::{raise_error, Result};
+use crate::common::location::{HasSpan, Span};
+use crate::parser::SymbolType;
+
+use super::ast::{Node, NodeS};
+use super::errors::{
+    ArithmeticOverflow, DivisionByZero, MissingOperator, TrailingOperator, UnknownOperator,
+};
+
+/// Binding of one binary operator: a larger `precedence` binds
+///     tighter, `right_assoc` makes a run of the same operator
+///     nest to the right.
+#[derive(Debug, Clone, Copy)]
+pub struct Operator {
+    pub symbol: &'static str,
+    pub precedence: u8,
+    pub right_assoc: bool,
+}
+
+/// Rebuilds a flat converted phrase into an expression tree
+///     honoring `operators`: `a + b * c` becomes the call
+///     `+(a, *(b, c))` - operator applications reuse
+///     `Node::Call`, so consumers need no new cases.
+/// A special chain missing from the table, two operands with no
+///     operator between them, and a leading or trailing operator
+///     are all errors with the offending span.
+pub fn build_expr_tree(phrase: &[NodeS], operators: &[Operator]) -> Result<NodeS> {
+    let mut pos = 0;
+    parse_binary(phrase, &mut pos, 0, operators)
+}
+
+fn parse_binary(
+    phrase: &[NodeS],
+    pos: &mut usize,
+    min: u8,
+    operators: &[Operator],
+) -> Result<NodeS> {
+    let mut lhs = operand(phrase, pos, operators)?;
+    while let Some(node) = phrase.get(*pos) {
+        let op = match operator(node, operators)? {
+            Some(op) if op.precedence >= min => op,
+            Some(_) => break,
+            // Two operands with nothing between them.
+            None => raise_error!(MissingOperator, node.span(),),
+        };
+        *pos += 1;
+        // Left associativity excludes equal precedence from the
+        //     right side, so `a - b - c` closes the first `-`.
+        let next_min = match op.right_assoc {
+            true => op.precedence,
+            false => op.precedence + 1,
+        };
+        let rhs = parse_binary(phrase, pos, next_min, operators)?;
+        let span = Span::cover(lhs.span(), rhs.span());
+        let callee = NodeS::new_c(vec![op.symbol.into()], node.span());
+        lhs = NodeS::new_call(callee, vec![lhs, rhs], span);
+    }
+    Ok(lhs)
+}
+
+fn operand(phrase: &[NodeS], pos: &mut usize, operators: &[Operator]) -> Result<NodeS> {
+    match phrase.get(*pos) {
+        Some(node) => match operator(node, operators)? {
+            // An operator where an operand belongs.
+            Some(_) => raise_error!(MissingOperator, node.span(),),
+            None => {
+                *pos += 1;
+                Ok(node.clone())
+            }
+        },
+        None => {
+            let span = phrase.last().map(|n| n.span()).unwrap_or_default();
+            raise_error!(TrailingOperator, span,)
+        }
+    }
+}
+
+// `parser2ast` turns a `Special` token into a single-symbol
+//     chain, so an operator is recognized by its spelling: all
+//     special chars. Identifier chains never qualify.
+fn operator<'table>(node: &NodeS, operators: &'table [Operator]) -> Result<Option<&'table Operator>> {
+    let symbol = match node.node() {
+        Node::Chain(chain) if chain.len() == 1 => chain[0],
+        _ => return Ok(None),
+    };
+    let text = symbol.to_string();
+    let special = |c: char| matches!(SymbolType::from(c), SymbolType::Special(_));
+    if text.is_empty() || !text.chars().all(special) {
+        return Ok(None);
+    }
+    match operators.iter().find(|op| op.symbol == text) {
+        Some(op) => Ok(Some(op)),
+        None => raise_error!(UnknownOperator, node.span(), text),
+    }
+}
+
+/// Collapses every purely-literal integer subtree of the
+///     expression tree `build_expr_tree` produces - `+`, `-`,
+///     `*` and `/` applications of two `Node::LitInt`s - into a
+///     single literal keeping the subtree's span, bottom-up, so
+///     `2 + 3 * 4` becomes `14`. Anything touching a non-literal
+///     stays as is. Division by zero and overflow are
+///     diagnostics at the subtree, not panics.
+pub fn fold_constants(node: &mut NodeS) -> Result<()> {
+    let span = node.span();
+    match node.node_mut() {
+        Node::Call(callee, args)
+        | Node::Index(callee, args)
+        | Node::Annotation(callee, args) => {
+            fold_constants(callee)?;
+            for sub in args {
+                fold_constants(sub)?
+            }
+        }
+        Node::Phrase(inner)
+        | Node::Bracket(_, inner)
+        | Node::Tuple(inner)
+        | Node::List(inner)
+        | Node::Set(inner) => {
+            for sub in inner {
+                fold_constants(sub)?
+            }
+        }
+        Node::Group(inner) => fold_constants(inner)?,
+        _ => {}
+    }
+    if let Some(value) = evaluate(node, span)? {
+        *node.node_mut() = Node::LitInt(value)
+    }
+    Ok(())
+}
+
+// The folded value of an operator application over two already
+//     folded literals, `None` when it isn't one.
+fn evaluate(node: &NodeS, span: Span) -> Result<Option<i64>> {
+    let (op, a, b) = match node.node() {
+        Node::Call(callee, args) => match (callee.node(), &args[..]) {
+            (Node::Chain(op), [a, b]) if op.len() == 1 => (op[0], a, b),
+            _ => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+    let (a, b) = match (a.node(), b.node()) {
+        (Node::LitInt(a), Node::LitInt(b)) => (*a, *b),
+        _ => return Ok(None),
+    };
+    let result = match op.to_string().as_str() {
+        "+" => a.checked_add(b),
+        "-" => a.checked_sub(b),
+        "*" => a.checked_mul(b),
+        "/" if b == 0 => raise_error!(DivisionByZero, span,),
+        "/" => a.checked_div(b),
+        _ => return Ok(None),
+    };
+    match result {
+        Some(value) => Ok(Some(value)),
+        None => raise_error!(ArithmeticOverflow, span,),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::error::ErrorKind;
+
+    const TABLE: &[Operator] = &[
+        Operator {
+            symbol: "+",
+            precedence: 1,
+            right_assoc: false,
+        },
+        Operator {
+            symbol: "-",
+            precedence: 1,
+            right_assoc: false,
+        },
+        Operator {
+            symbol: "*",
+            precedence: 2,
+            right_assoc: false,
+        },
+        Operator {
+            symbol: "->",
+            precedence: 0,
+            right_assoc: true,
+        },
+    ];
+
+    fn phrase(src: &str) -> Vec<NodeS> {
+        let file = crate::common::location::File::new_str("".into(), src).unwrap();
+        let parsed = crate::parser::parse(&file).unwrap();
+        let lines = crate::glue::parser2ast::parser2ast(&parsed).unwrap();
+        match lines[0].line().node() {
+            Node::Phrase(phrase) => phrase.clone(),
+            other => panic!("not a phrase: {:?}", other),
+        }
+    }
+
+    // `(op, lhs, rhs)` of an operator application.
+    fn call(node: &NodeS) -> (String, &NodeS, &NodeS) {
+        match node.node() {
+            Node::Call(callee, args) => match (callee.node(), &args[..]) {
+                (Node::Chain(op), [lhs, rhs]) => (op[0].to_string(), lhs, rhs),
+                other => panic!("not an operator call: {:?}", other),
+            },
+            other => panic!("not a call: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        // `a + b * c` -> `+(a, *(b, c))`.
+        let tree = build_expr_tree(&phrase("a + b * c\n"), TABLE).unwrap();
+        let (op, lhs, rhs) = call(&tree);
+        assert_eq!(op, "+");
+        assert!(matches!(lhs.node(), Node::Chain(_)));
+        assert_eq!(call(rhs).0, "*");
+        // Left associativity: `a - b - c` -> `-(-(a, b), c)`.
+        let tree = build_expr_tree(&phrase("a - b - c\n"), TABLE).unwrap();
+        let (_, lhs, _) = call(&tree);
+        assert_eq!(call(lhs).0, "-");
+        // Right associativity: `a -> b -> c` -> `->(a, ->(b, c))`.
+        let tree = build_expr_tree(&phrase("a -> b -> c\n"), TABLE).unwrap();
+        let (_, _, rhs) = call(&tree);
+        assert_eq!(call(rhs).0, "->");
+        // The tree covers the whole phrase.
+        assert_eq!(tree.span().begin().as_usize(), 0);
+        assert_eq!(tree.span().end().as_usize(), 11);
+    }
+
+    // `TABLE` plus `/`, which `malformed_phrases` needs absent.
+    fn fold_table() -> Vec<Operator> {
+        let div = Operator {
+            symbol: "/",
+            precedence: 2,
+            right_assoc: false,
+        };
+        TABLE.iter().copied().chain([div]).collect()
+    }
+
+    #[test]
+    fn folds_literal_subtrees() {
+        let mut tree = build_expr_tree(&phrase("2 + 3 * 4\n"), TABLE).unwrap();
+        fold_constants(&mut tree).unwrap();
+        assert!(matches!(tree.node(), Node::LitInt(14)));
+        // The literal keeps the span of the subtree it replaced.
+        assert_eq!(tree.span().begin().as_usize(), 0);
+        assert_eq!(tree.span().end().as_usize(), 9);
+        // A non-literal operand stops folding at its level only.
+        let mut tree = build_expr_tree(&phrase("a + 2 * 3\n"), TABLE).unwrap();
+        fold_constants(&mut tree).unwrap();
+        let (op, _, rhs) = call(&tree);
+        assert_eq!(op, "+");
+        assert!(matches!(rhs.node(), Node::LitInt(6)));
+    }
+
+    // Annotation arguments fold like call arguments.
+    #[test]
+    fn folds_annotation_arguments() {
+        let arg = build_expr_tree(&phrase("2 + 3\n"), TABLE).unwrap();
+        let span = arg.span();
+        let name = NodeS::new_c(vec!["cfg".into()], span);
+        let mut annotation = NodeS::new_annotation(name, vec![arg], span);
+        fold_constants(&mut annotation).unwrap();
+        match annotation.node() {
+            Node::Annotation(_, args) => assert!(matches!(args[0].node(), Node::LitInt(5))),
+            other => panic!("not an annotation: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn fold_diagnostics() {
+        let kind = |src| {
+            let mut tree = build_expr_tree(&phrase(src), &fold_table()).unwrap();
+            fold_constants(&mut tree).unwrap_err().kind()
+        };
+        assert_eq!(kind("1 / 0\n"), ErrorKind::DivisionByZero);
+        assert_eq!(
+            kind("9223372036854775807 + 1\n"),
+            ErrorKind::ArithmeticOverflow
+        );
+    }
+
+    #[test]
+    fn malformed_phrases() {
+        let kind = |src| build_expr_tree(&phrase(src), TABLE).unwrap_err().kind();
+        assert_eq!(kind("a b\n"), ErrorKind::MissingOperator);
+        assert_eq!(kind("+ a\n"), ErrorKind::MissingOperator);
+        assert_eq!(kind("a +\n"), ErrorKind::TrailingOperator);
+        assert_eq!(kind("a / b\n"), ErrorKind::UnknownOperator);
+        // A lone operand needs no operators at all.
+        let tree = build_expr_tree(&phrase("a\n"), TABLE).unwrap();
+        assert!(matches!(tree.node(), Node::Chain(_)));
+    }
+}
@@ -1,5 +1,13 @@
 // TODO: remove `pub`.
 pub mod ast;
 pub mod context;
+pub mod errors;
+pub mod expr;
+pub mod symbols;
+pub mod visit;
 
 pub use ast::Project;
+pub use expr::{build_expr_tree, fold_constants, Operator};
+pub use ast::{MemoryCache, ParseCache};
+pub use symbols::Scope;
+pub use visit::{Visitor, VisitorMut};
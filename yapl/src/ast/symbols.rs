@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+
+use crate::common::error::Error;
+use crate::common::location::{HasSpan, Position, Span};
+use crate::common::symbol::Symbol;
+
+use super::ast::{Line, Node, NodeS, Project};
+use super::errors::DuplicateDefinition;
+
+/// Scope tree built from a `Project`.
+/// A line's block and every bracket introduce a nested scope;
+///     the head chain of a phrase defines a name in its scope.
+#[derive(Debug, Default)]
+pub struct Scope {
+    span: Span,
+    definitions: HashMap<Symbol, Span>,
+    children: Vec<Scope>,
+}
+
+pub fn build(project: &Project) -> (Scope, Vec<Error>) {
+    let mut errors = Vec::new();
+    let mut root = Scope::default();
+    for line in project.roots() {
+        collect_line(&mut root, line, &mut errors)
+    }
+    (root, errors)
+}
+
+impl Scope {
+    fn with_span(span: Span) -> Self {
+        Self {
+            span,
+            ..Default::default()
+        }
+    }
+
+    /// The definition `name` resolves to at `position`:
+    ///     the deepest enclosing scope wins.
+    pub fn resolve(&self, name: Symbol, position: Position) -> Option<Span> {
+        for child in &self.children {
+            if child.span.begin() <= position && position <= child.span.end() {
+                if let Some(found) = child.resolve(name, position) {
+                    return Some(found);
+                }
+            }
+        }
+        self.definitions.get(&name).copied()
+    }
+}
+
+fn define(scope: &mut Scope, symbol: Symbol, span: Span, errors: &mut Vec<Error>) {
+    if let Some(&previous) = scope.definitions.get(&symbol) {
+        errors.push(Box::new(DuplicateDefinition::new(
+            span,
+            symbol.to_string(),
+            previous,
+        )));
+        return;
+    }
+    scope.definitions.insert(symbol, span);
+}
+
+fn collect_line(scope: &mut Scope, line: &Line, errors: &mut Vec<Error>) {
+    if let Node::Phrase(items) = line.line().node() {
+        collect_phrase(scope, items, errors)
+    }
+    for extension in line.extension() {
+        collect_line(scope, extension, errors)
+    }
+    if let (Some(first), Some(last)) = (line.block().first(), line.block().last()) {
+        let mut child = Scope::with_span(Span::new(first.span().begin(), last.span().end()));
+        for sub in line.block() {
+            collect_line(&mut child, sub, errors)
+        }
+        scope.children.push(child)
+    }
+}
+
+fn collect_phrase(scope: &mut Scope, items: &[NodeS], errors: &mut Vec<Error>) {
+    for (i, item) in items.iter().enumerate() {
+        match item.node() {
+            Node::Chain(chain) if i == 0 => {
+                if let Some(&first) = chain.first() {
+                    define(scope, first, item.span(), errors)
+                }
+            }
+            Node::Bracket(_, inner) => {
+                let mut child = Scope::with_span(item.span());
+                for part in inner {
+                    if let Node::Phrase(sub) = part.node() {
+                        collect_phrase(&mut child, sub, errors)
+                    }
+                }
+                scope.children.push(child)
+            }
+            Node::Phrase(sub) => collect_phrase(scope, sub, errors),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn span(begin: usize, end: usize) -> Span {
+        Span::new(Position::new(begin).unwrap(), Position::new(end).unwrap())
+    }
+
+    fn line(name: &'static str, at: Span, block: Vec<Line>) -> Line {
+        let head = NodeS::new_c(vec![name.into()], at);
+        Line::new(NodeS::new_p(vec![head], at), Vec::new(), block, at)
+    }
+
+    #[test]
+    fn resolve_and_duplicates() {
+        let inner = line("x", span(10, 11), Vec::new());
+        let outer = line("x", span(0, 1), vec![inner]);
+        let project = Project::new(vec![outer, line("y", span(20, 21), Vec::new())]);
+
+        let (scope, errors) = build(&project);
+        assert!(errors.is_empty());
+        // Inside the block the inner `x` shadows the outer one.
+        let x: Symbol = "x".into();
+        assert_eq!(scope.resolve(x, Position::new(10).unwrap()), Some(span(10, 11)));
+        assert_eq!(scope.resolve(x, Position::new(20).unwrap()), Some(span(0, 1)));
+
+        let project = Project::new(vec![
+            line("y", span(0, 1), Vec::new()),
+            line("y", span(2, 3), Vec::new()),
+        ]);
+        let (_, errors) = build(&project);
+        assert_eq!(errors.len(), 1);
+    }
+}
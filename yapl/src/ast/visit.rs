@@ -0,0 +1,148 @@
+use super::ast::{Line, Node, NodeS, Project};
+
+/// Read-only traversal of ast.
+/// `visit_*` methods default to plain recursion,
+///     so implementors override only what they need.
+pub trait Visitor {
+    fn visit_project(&mut self, project: &Project) {
+        walk_project(self, project)
+    }
+    fn visit_line(&mut self, line: &Line) {
+        walk_line(self, line)
+    }
+    fn visit_node(&mut self, node: &NodeS) {
+        walk_node(self, node)
+    }
+}
+
+/// Mutating traversal of ast, for transformations.
+pub trait VisitorMut {
+    fn visit_project(&mut self, project: &mut Project) {
+        walk_project_mut(self, project)
+    }
+    fn visit_line(&mut self, line: &mut Line) {
+        walk_line_mut(self, line)
+    }
+    fn visit_node(&mut self, node: &mut NodeS) {
+        walk_node_mut(self, node)
+    }
+}
+
+pub fn walk_project<V: Visitor + ?Sized>(visitor: &mut V, project: &Project) {
+    for line in project.roots() {
+        visitor.visit_line(line)
+    }
+}
+
+pub fn walk_line<V: Visitor + ?Sized>(visitor: &mut V, line: &Line) {
+    visitor.visit_node(line.line());
+    for sub in line.extension().iter().chain(line.block()) {
+        visitor.visit_line(sub)
+    }
+}
+
+pub fn walk_node<V: Visitor + ?Sized>(visitor: &mut V, node: &NodeS) {
+    match node.node() {
+        Node::Phrase(inner)
+        | Node::Bracket(_, inner)
+        | Node::Tuple(inner)
+        | Node::List(inner)
+        | Node::Set(inner) => {
+            for sub in inner {
+                visitor.visit_node(sub)
+            }
+        }
+        Node::Call(callee, args) | Node::Index(callee, args) | Node::Annotation(callee, args) => {
+            visitor.visit_node(callee);
+            for sub in args {
+                visitor.visit_node(sub)
+            }
+        }
+        Node::Group(inner) => visitor.visit_node(inner),
+        Node::Chain(_)
+        | Node::Keyword(_)
+        | Node::LitStr(_)
+        | Node::LitInt(_)
+        | Node::LitFloat(_)
+        | Node::LitChar(_)
+        | Node::LitBool(_) => {}
+    }
+}
+
+pub fn walk_project_mut<V: VisitorMut + ?Sized>(visitor: &mut V, project: &mut Project) {
+    for line in project.roots_mut() {
+        visitor.visit_line(line)
+    }
+}
+
+pub fn walk_line_mut<V: VisitorMut + ?Sized>(visitor: &mut V, line: &mut Line) {
+    visitor.visit_node(line.line_mut());
+    for sub in line.extension_mut() {
+        visitor.visit_line(sub)
+    }
+    for sub in line.block_mut() {
+        visitor.visit_line(sub)
+    }
+}
+
+pub fn walk_node_mut<V: VisitorMut + ?Sized>(visitor: &mut V, node: &mut NodeS) {
+    match node.node_mut() {
+        Node::Phrase(inner)
+        | Node::Bracket(_, inner)
+        | Node::Tuple(inner)
+        | Node::List(inner)
+        | Node::Set(inner) => {
+            for sub in inner {
+                visitor.visit_node(sub)
+            }
+        }
+        Node::Call(callee, args) | Node::Index(callee, args) | Node::Annotation(callee, args) => {
+            visitor.visit_node(callee);
+            for sub in args {
+                visitor.visit_node(sub)
+            }
+        }
+        Node::Group(inner) => visitor.visit_node(inner),
+        Node::Chain(_)
+        | Node::Keyword(_)
+        | Node::LitStr(_)
+        | Node::LitInt(_)
+        | Node::LitFloat(_)
+        | Node::LitChar(_)
+        | Node::LitBool(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::location::Span;
+
+    // Example analysis: count all integer literals in a `Project`.
+    #[derive(Default)]
+    struct CountInts(usize);
+
+    impl Visitor for CountInts {
+        fn visit_node(&mut self, node: &NodeS) {
+            if let Node::LitInt(_) = node.node() {
+                self.0 += 1
+            }
+            walk_node(self, node)
+        }
+    }
+
+    #[test]
+    fn count_int_literals() {
+        let span = Span::default();
+        let phrase = NodeS::new_p(
+            vec![NodeS::new_li(1, span), NodeS::new_li(2, span)],
+            span,
+        );
+        let line = Line::new(phrase, Vec::new(), Vec::new(), span);
+        let project = Project::new(vec![line]);
+
+        let mut counter = CountInts::default();
+        counter.visit_project(&project);
+        assert_eq!(counter.0, 2);
+    }
+}
@@ -1,5 +1,224 @@
-pub trait IsError: super::location::HasSpan + std::fmt::Debug {
+/// Machine-matchable classification of every diagnostic, one
+///     variant per `error_struct!` type. Any data (the offending
+///     char, the expected offset) stays on the struct itself;
+///     the kind is for tooling that reacts per diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    UnsupportedSymbol,
+    UnexpectedEOS,
+    UnexpectedSymbol,
+    ParseInt,
+    ParseFloat,
+    InvalidUnicodeEscape,
+    EmptyCharLiteral,
+    ClosedBracket,
+    MismatchedBracket,
+    ClosingBracketNotFound,
+    UnexpectedToken,
+    /// No longer produced - `EmptyPartBeforeComma` replaced it.
+    /// Kept so the `E0012` code stays reserved.
+    EmptyPartInBrackets,
+    UnexpectedEndOfLine,
+    WrongLineOffset,
+    NewLineOnFileEnd,
+    MixedIndentation,
+    UnterminatedComment,
+    ReadFailed,
+    ToBeDone,
+    DuplicateDefinition,
+    DedentMismatch,
+    EmptyPartBeforeComma,
+    NestingTooDeep,
+    MissingOperator,
+    TrailingOperator,
+    UnknownOperator,
+    DivisionByZero,
+    ArithmeticOverflow,
+    TrailingComma,
+    TabIndentation,
+    LineTooLong,
+}
+
+impl ErrorKind {
+    /// Stable diagnostic code. Codes are never renumbered once
+    ///     assigned - new kinds take the next free number.
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::UnsupportedSymbol => "E0001",
+            Self::UnexpectedEOS => "E0002",
+            Self::UnexpectedSymbol => "E0003",
+            Self::ParseInt => "E0004",
+            Self::ParseFloat => "E0005",
+            Self::InvalidUnicodeEscape => "E0006",
+            Self::EmptyCharLiteral => "E0007",
+            Self::ClosedBracket => "E0008",
+            Self::MismatchedBracket => "E0009",
+            Self::ClosingBracketNotFound => "E0010",
+            Self::UnexpectedToken => "E0011",
+            Self::EmptyPartInBrackets => "E0012",
+            Self::UnexpectedEndOfLine => "E0013",
+            Self::WrongLineOffset => "E0014",
+            Self::NewLineOnFileEnd => "E0015",
+            Self::MixedIndentation => "E0016",
+            Self::UnterminatedComment => "E0017",
+            Self::ReadFailed => "E0018",
+            Self::ToBeDone => "E0019",
+            Self::DuplicateDefinition => "E0020",
+            Self::DedentMismatch => "E0021",
+            Self::EmptyPartBeforeComma => "E0022",
+            Self::NestingTooDeep => "E0023",
+            Self::MissingOperator => "E0024",
+            Self::TrailingOperator => "E0025",
+            Self::UnknownOperator => "E0026",
+            Self::DivisionByZero => "E0027",
+            Self::ArithmeticOverflow => "E0028",
+            Self::TrailingComma => "W0001",
+            Self::TabIndentation => "W0002",
+            Self::LineTooLong => "W0003",
+        }
+    }
+}
+
+/// Whether a diagnostic fails the parse or merely flags
+///     something questionable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// How diagnostic columns are counted: by chars (the default,
+///     matching the parser's positions) or by display width, for
+///     terminals where wide characters occupy two cells - see
+///     `Position::get_line_and_column`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColumnMode {
+    #[default]
+    Chars,
+    DisplayWidth,
+}
+
+// `Send` so diagnostics can cross thread boundaries when files
+//     parse in parallel.
+pub trait IsError: super::location::HasSpan + std::fmt::Debug + Send {
     fn message(&self) -> String;
+
+    fn kind(&self) -> ErrorKind;
+
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn code(&self) -> &'static str {
+        self.kind().code()
+    }
+
+    /// rustc-like rendering: the offending line, a caret underline
+    ///     below the span, and the message.
+    /// Multi-line spans underline only the first line, with `...`
+    ///     marking the continuation.
+    fn render(&self, file: &super::location::File) -> String {
+        self.render_with(file, ColumnMode::Chars)
+    }
+
+    /// `render` with an explicit column unit: `DisplayWidth`
+    ///     reports the header column in display columns. The
+    ///     snippet and its underline are unaffected.
+    fn render_with(&self, file: &super::location::File, columns: ColumnMode) -> String {
+        let severity = match self.severity() {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        let span = self.span();
+        let (line_num, offset) = match span.begin().get_line_and_offset(file) {
+            Some(v) => v,
+            None => return format!("{}[{}]: {}", severity, self.code(), self.message()),
+        };
+        let column = match columns {
+            ColumnMode::Chars => offset,
+            ColumnMode::DisplayWidth => span
+                .begin()
+                .get_line_and_column(file)
+                .map_or(offset, |(_, c)| c),
+        };
+        let text = file.line(line_num).unwrap_or("");
+        let (end, continued) = match span.end().get_line_and_offset(file) {
+            Some((l, o)) if l == line_num => (o, false),
+            _ => (text.chars().count(), true),
+        };
+        let underline = match end.max(offset + 1) - offset {
+            1 => "^".to_string(),
+            n => format!("^{}", "~".repeat(n - 1)),
+        };
+        let number = (line_num + 1).to_string();
+        let pad = " ".repeat(number.len());
+        format!(
+            "{}[{}]: {}\n{}--> {}:{}:{}\n{} |\n{} | {}\n{} | {}{}{}",
+            severity,
+            self.code(),
+            self.message(),
+            pad,
+            file.get_path().display(),
+            line_num + 1,
+            column + 1,
+            pad,
+            number,
+            text,
+            pad,
+            " ".repeat(offset),
+            underline,
+            if continued { "..." } else { "" },
+        )
+    }
+
+    /// JSON form for CI consumption, schema version 1: `{"file",
+    ///     "start": {"line", "column"}, "end": {"line", "column"},
+    ///     "severity", "code", "message"}`. Lines and columns are
+    ///     1-based, as most tools expect; a position outside the
+    ///     file becomes `null`. The schema only grows - breaking
+    ///     changes bump `version` in `diagnostics_to_json`.
+    fn to_json(&self, file: &super::location::File) -> serde_json::Value {
+        self.to_json_with(file, ColumnMode::Chars)
+    }
+
+    /// `to_json` with an explicit column unit - the schema is
+    ///     unchanged, only the column values are.
+    fn to_json_with(&self, file: &super::location::File, columns: ColumnMode) -> serde_json::Value {
+        let position = |p: super::location::Position| {
+            let at = match columns {
+                ColumnMode::Chars => p.get_line_and_offset(file),
+                ColumnMode::DisplayWidth => p.get_line_and_column(file),
+            };
+            match at {
+                Some((line, column)) => serde_json::json!({"line": line + 1, "column": column + 1}),
+                None => serde_json::Value::Null,
+            }
+        };
+        serde_json::json!({
+            "file": file.get_path().display().to_string(),
+            "start": position(self.span().begin()),
+            "end": position(self.span().end()),
+            "severity": match self.severity() {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            },
+            "code": self.code(),
+            "message": self.message(),
+        })
+    }
+}
+
+/// The batch form of `IsError::to_json`:
+///     `{"version": 1, "diagnostics": [...]}`.
+pub fn diagnostics_to_json(errors: &[Error], file: &super::location::File) -> serde_json::Value {
+    let diagnostics: Vec<_> = errors.iter().map(|e| e.to_json(file)).collect();
+    serde_json::json!({"version": 1, "diagnostics": diagnostics})
+}
+
+impl std::fmt::Display for dyn IsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message())
+    }
 }
 
 pub type Error = Box<dyn IsError>;
@@ -22,12 +241,48 @@ macro_rules! error_struct {
             fn message(&self) -> String {
                 format!($message, $(self.$data),*)
             }
+
+            fn kind(&self) -> crate::common::error::ErrorKind {
+                crate::common::error::ErrorKind::$name
+            }
         }
     };
 }
 
 pub(crate) use error_struct;
 
+// Same shape as `error_struct!`, but the diagnostic doesn't fail
+//     the parse - it's accumulated and handed back on success.
+macro_rules! warning_struct {
+    ($name:ident, $message:literal, $($data:ident: $ty:ty),*) => {
+        #[derive(derive_new::new, Debug)]
+        pub struct $name {
+            span: crate::common::location::Span,
+            $($data: $ty),*
+        }
+
+        impl crate::common::location::HasSpan for $name {
+            fn span(&self) -> crate::common::location::Span {
+                self.span
+            }
+        }
+        impl crate::common::error::IsError for $name {
+            fn message(&self) -> String {
+                format!($message, $(self.$data),*)
+            }
+
+            fn kind(&self) -> crate::common::error::ErrorKind {
+                crate::common::error::ErrorKind::$name
+            }
+
+            fn severity(&self) -> crate::common::error::Severity {
+                crate::common::error::Severity::Warning
+            }
+        }
+    };
+}
+pub(crate) use warning_struct;
+
 // To be done: get rid of trailing comma in case with no `data`.
 macro_rules! raise_error {
     ($name:ident, $span:expr, $($data:expr),*) => {
@@ -41,3 +296,117 @@ macro_rules! raise_error_on {
     };
 }
 pub(crate) use raise_error_on;
+
+/// Adapter giving the crate's errors a `miette::Diagnostic` face.
+/// Additive: the default `Result`/`Error` API is untouched.
+#[cfg(feature = "miette")]
+mod diagnostic {
+    use super::Error;
+    use crate::common::location::File;
+
+    #[derive(Debug)]
+    pub struct Diagnostic {
+        message: String,
+        label: miette::SourceSpan,
+        source: miette::NamedSource,
+    }
+
+    impl Diagnostic {
+        /// Maps the char-based `Span` onto miette's byte-based
+        ///     `SourceSpan` against `file`.
+        pub fn new(error: &Error, file: &File) -> Self {
+            let span = error.span();
+            let begin = file.byte_offset(span.begin());
+            let len = file.byte_offset(span.end()) - begin;
+            Self {
+                message: error.message(),
+                label: (begin, len).into(),
+                source: miette::NamedSource::new(
+                    file.get_path().to_string_lossy(),
+                    file.code().clone(),
+                ),
+            }
+        }
+    }
+
+    impl std::fmt::Display for Diagnostic {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str(&self.message)
+        }
+    }
+
+    impl std::error::Error for Diagnostic {}
+
+    impl miette::Diagnostic for Diagnostic {
+        fn source_code(&self) -> Option<&dyn miette::SourceCode> {
+            Some(&self.source)
+        }
+
+        fn labels(&self) -> Option<Box<dyn Iterator<Item = miette::LabeledSpan> + '_>> {
+            let label = miette::LabeledSpan::new_with_span(None, self.label);
+            Some(Box::new(std::iter::once(label)))
+        }
+    }
+}
+#[cfg(feature = "miette")]
+pub use diagnostic::Diagnostic;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::location::{File, Position, Span};
+    use crate::parser::errors::UnexpectedEOS;
+
+    #[test]
+    fn render_snippet() {
+        let file = File::new_reader("f x\ng yyy z\n".as_bytes()).unwrap();
+        let begin = Position::new(6).unwrap();
+        let error = UnexpectedEOS::new(Span::new(begin, begin.advanced(3)));
+        let rendered = error.render(&file);
+        assert!(rendered.starts_with("error[E0002]: EOS wasn't expected here\n"));
+        assert!(rendered.contains("2 | g yyy z"));
+        assert!(rendered.ends_with("  |   ^~~"));
+        // Warnings render under their own prefix.
+        let warning = crate::parser::errors::TrailingComma::new(Span::new(begin, begin));
+        assert!(warning.render(&file).starts_with("warning[W0001]:"));
+    }
+
+    #[test]
+    fn json_diagnostics() {
+        let file = File::new_reader("f x\ng yyy z\n".as_bytes()).unwrap();
+        let begin = Position::new(6).unwrap();
+        let error: Error = Box::new(UnexpectedEOS::new(Span::new(begin, begin.advanced(3))));
+        let json = error.to_json(&file);
+        assert_eq!(json["code"], "E0002");
+        assert_eq!(json["severity"], "error");
+        assert_eq!(json["start"]["line"], 2);
+        assert_eq!(json["start"]["column"], 3);
+        assert_eq!(json["end"]["column"], 6);
+        let batch = diagnostics_to_json(&[error], &file);
+        assert_eq!(batch["version"], 1);
+        assert_eq!(batch["diagnostics"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn display_width_columns() {
+        let file = File::new_reader("日本 x\n".as_bytes()).unwrap();
+        let begin = Position::new(3).unwrap(); // The `x`.
+        let error: Error = Box::new(UnexpectedEOS::new(Span::new(begin, begin.advanced(1))));
+        // Wide characters take two display columns.
+        let json = error.to_json_with(&file, ColumnMode::DisplayWidth);
+        assert_eq!(json["start"]["column"], 6);
+        assert!(error
+            .render_with(&file, ColumnMode::DisplayWidth)
+            .contains(":1:6"));
+        // The default stays char-based.
+        assert_eq!(error.to_json(&file)["start"]["column"], 4);
+    }
+
+    #[test]
+    fn kind_matching() {
+        let error: Error = Box::new(UnexpectedEOS::new(Default::default()));
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEOS);
+        assert_eq!(error.code(), "E0002");
+        assert_eq!(error.to_string(), error.message());
+    }
+}
@@ -10,6 +10,9 @@ pub struct File {
     #[getset(get = "pub")]
     code: String,
     lines: Vec<String>,
+    /// Byte and char offset of every line start - lets the
+    ///     byte<->char conversions below run in O(log lines).
+    line_starts: Vec<(usize, usize)>,
 }
 
 const MAX_FILE_SIZE: usize = 60000;
@@ -17,16 +20,117 @@ const MAX_FILE_SIZE: usize = 60000;
 impl File {
     pub fn new_read(path: PathBuf) -> Result<Self, String> {
         let code = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        Self::new_code(code, path)
+    }
+
+    /// Reads the whole `reader`. The input must be UTF-8,
+    ///     anything else is reported as an error.
+    pub fn new_reader<R: std::io::Read>(mut reader: R) -> Result<Self, String> {
+        let mut code = String::new();
+        reader
+            .read_to_string(&mut code)
+            .map_err(|e| e.to_string())?;
+        Self::new_code(code, PathBuf::new())
+    }
+
+    /// An in-memory source; `path` is used only for diagnostics.
+    pub fn new_str(path: PathBuf, code: &str) -> Result<Self, String> {
+        Self::new_code(code.to_string(), path)
+    }
+
+    // A leading UTF-8 BOM is dropped and CRLF and bare-CR line
+    //     endings are normalized to LF first, so positions are
+    //     identical however the file was saved.
+    fn new_code(code: String, path: PathBuf) -> Result<Self, String> {
+        let code = match code.strip_prefix('\u{feff}') {
+            Some(stripped) => stripped.to_string(),
+            None => code,
+        };
+        let code = match code.contains('\r') {
+            true => code.replace("\r\n", "\n").replace('\r', "\n"),
+            false => code,
+        };
         if code.len() > MAX_FILE_SIZE {
             return Err(format!("to long file {:?}", path.as_os_str()));
         }
         let lines: Vec<_> = code.lines().map(|s| s.to_string()).collect();
-        Ok(Self { path, code, lines })
+        let mut line_starts = vec![(0, 0)];
+        let mut chars = 0;
+        for (bytes, c) in code.char_indices() {
+            chars += 1;
+            if c == '\n' {
+                line_starts.push((bytes + 1, chars))
+            }
+        }
+        Ok(Self {
+            path,
+            code,
+            lines,
+            line_starts,
+        })
     }
 
     pub fn get_path(&self) -> &Path {
         self.path.as_path()
     }
+
+    /// Text of line `line_num` (0-based), without the newline.
+    pub fn line(&self, line_num: usize) -> Option<&str> {
+        self.lines.get(line_num).map(|s| s.as_str())
+    }
+
+    /// The `Position` of `byte_offset` into `code`. Positions count
+    ///     chars, matching how the lexer advances, so multi-byte
+    ///     UTF-8 is converted explicitly. `None` when the offset is
+    ///     out of range or not on a char boundary.
+    pub fn position_at(&self, byte_offset: usize) -> Option<Position> {
+        let line = self.line_starts.partition_point(|&(b, _)| b <= byte_offset) - 1;
+        let (line_bytes, line_chars) = self.line_starts[line];
+        let within = self.code.get(line_bytes..byte_offset)?.chars().count();
+        Position::new(line_chars + within)
+    }
+
+    /// `byte_offset` under the name byte-oriented APIs expect.
+    pub fn to_byte(&self, position: Position) -> usize {
+        self.byte_offset(position)
+    }
+
+    /// Inverse of `position_at`.
+    pub fn byte_offset(&self, position: Position) -> usize {
+        let pos = position.as_usize();
+        let line = self.line_starts.partition_point(|&(_, c)| c <= pos) - 1;
+        let (line_bytes, line_chars) = self.line_starts[line];
+        line_bytes
+            + self.code[line_bytes..]
+                .chars()
+                .take(pos - line_chars)
+                .map(char::len_utf8)
+                .sum::<usize>()
+    }
+
+    /// The exact source text `span` covers, newlines included
+    ///     for multi-line spans. A zero-width span yields `""`;
+    ///     bounds past the file clamp to its end rather than
+    ///     panicking.
+    pub fn slice(&self, span: &Span) -> &str {
+        self.code.get(span.byte_range(self)).unwrap_or("")
+    }
+
+    /// Returns a copy with line `line_num` (0-based) replaced.
+    pub fn with_line_replaced(&self, line_num: usize, new_text: &str) -> Result<Self, String> {
+        let mut lines: Vec<&str> = self.code.split('\n').collect();
+        match lines.get_mut(line_num) {
+            Some(line) => *line = new_text,
+            None => {
+                return Err(format!(
+                    "no line {} in {:?}",
+                    line_num,
+                    self.path.as_os_str()
+                ))
+            }
+        }
+        Self::new_code(lines.join("\n"), self.path.clone())
+    }
 }
 
 impl HasSpan for File {
@@ -39,38 +143,135 @@ impl HasSpan for File {
 // Note: don't forget, it has mean only in one `Context`.
 #[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct Position {
-    pos: u16,
+    pos: u32,
 }
 
 impl Position {
     pub fn new(pos: usize) -> Option<Self> {
         match pos {
             pos if pos > MAX_FILE_SIZE => None,
-            pos => Some(Self { pos: pos as u16 }),
+            pos => Some(Self { pos: pos as u32 }),
         }
     }
 
+    pub fn as_usize(&self) -> usize {
+        self.pos as usize
+    }
+
+    pub fn shift(&mut self, delta: isize) {
+        self.pos = (self.pos as isize + delta) as u32
+    }
+
     pub fn advance(&mut self, shift: usize) {
-        self.pos += shift as u16
+        self.pos += shift as u32
     }
     pub fn advanced(&self, shift: usize) -> Self {
         Position {
-            pos: self.pos + shift as u16,
+            pos: self.pos + shift as u32,
         }
     }
 
-    // To be done: sublinear algorithm.
     // Note: don't forget, it has mean only in one `Context`.
     pub fn get_line_and_offset(&self, context: &File) -> Option<(usize, usize)> {
-        let mut pos = self.pos as usize;
-        for (line_number, line) in context.lines.iter().enumerate() {
-            if pos >= line.len() {
-                pos -= line.len();
-            } else {
-                return Some((line_number, pos));
+        let pos = self.pos as usize;
+        let line = context.line_starts.partition_point(|&(_, c)| c <= pos) - 1;
+        let offset = pos - context.line_starts[line].1;
+        match offset <= context.lines.get(line)?.chars().count() {
+            true => Some((line, offset)),
+            false => None,
+        }
+    }
+
+    /// `line:col` rendering of `self` within `file`, 1-based as
+    ///     editors expect, while the internal offset stays 0-based.
+    /// A position doesn't know its file, so it is passed here
+    ///     once instead of being carried around. Out-of-range
+    ///     positions fall back to the raw char offset (`@12`).
+    pub fn display<'f>(&self, file: &'f File) -> DisplayPosition<'f> {
+        DisplayPosition {
+            position: *self,
+            file,
+        }
+    }
+
+    /// Like `get_line_and_offset`, but the second value counts
+    ///     display columns instead of chars - for editors that
+    ///     align diagnostics visually. Parsing itself still
+    ///     indexes by chars.
+    pub fn get_line_and_column(&self, context: &File) -> Option<(usize, usize)> {
+        let (line, offset) = self.get_line_and_offset(context)?;
+        let column = context.lines[line]
+            .chars()
+            .take(offset)
+            .map(char_width)
+            .sum();
+        Some((line, column))
+    }
+}
+
+/// See `Position::display`.
+pub struct DisplayPosition<'f> {
+    position: Position,
+    file: &'f File,
+}
+
+impl std::fmt::Display for DisplayPosition<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.position.get_line_and_offset(self.file) {
+            Some((line, col)) => write!(f, "{}:{}", line + 1, col + 1),
+            None => write!(f, "@{}", self.position.as_usize()),
+        }
+    }
+}
+
+/// See `Span::display`.
+pub struct DisplaySpan<'f> {
+    span: Span,
+    file: &'f File,
+}
+
+impl std::fmt::Display for DisplaySpan<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let begin = self.span.begin.get_line_and_offset(self.file);
+        let end = self.span.end.get_line_and_offset(self.file);
+        match (begin, end) {
+            // `end` is one past the span, so its 1-based column is
+            //     `col + 1` like the others.
+            (Some((line, col)), Some((e_line, e_col))) if line == e_line => {
+                write!(f, "{}:{}..{}", line + 1, col + 1, e_col + 1)
+            }
+            (Some((line, col)), Some((e_line, e_col))) => {
+                write!(f, "{}:{}-{}:{}", line + 1, col + 1, e_line + 1, e_col + 1)
             }
+            _ => write!(
+                f,
+                "@{}..@{}",
+                self.span.begin.as_usize(),
+                self.span.end.as_usize()
+            ),
         }
-        None
+    }
+}
+
+// Display width per the `unicode-width` rules: wide East Asian
+//     characters and emoji take two columns, combining marks none.
+fn char_width(c: char) -> usize {
+    unicode_width::UnicodeWidthChar::width(c).unwrap_or(0)
+}
+
+/// Handle of a file within a `Project` - cheap to copy and
+///     stamped onto spans so cross-file diagnostics stay
+///     unambiguous. Single-file usage keeps the default id 0.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FileId(u32);
+
+impl FileId {
+    pub fn new(id: usize) -> Self {
+        Self(id as u32)
+    }
+
+    pub fn as_usize(&self) -> usize {
+        self.0 as usize
     }
 }
 
@@ -85,17 +286,107 @@ pub struct Span {
     begin: Position,
     #[getset(get_copy = "pub")]
     end: Position,
+    #[getset(get_copy = "pub")]
+    #[serde(default)]
+    file: FileId,
 }
 
 impl Span {
     pub fn new(begin: Position, end: Position) -> Self {
         assert!(begin <= end);
-        Self { begin, end }
+        Self {
+            begin,
+            end,
+            file: Default::default(),
+        }
+    }
+
+    /// Same bounds within file `file`.
+    pub fn in_file(self, file: FileId) -> Self {
+        Self { file, ..self }
     }
 
     pub fn contains(&self, inner: Span) -> bool {
         self.begin <= inner.begin && self.end >= inner.end
     }
+
+    /// Whether the cursor position `pos` falls within the span.
+    /// Half-open: `begin` is inside, `end` (one past the last
+    ///     char) is not. Positions count chars through the whole
+    ///     file, so multi-line spans need no line arithmetic.
+    pub fn contains_position(&self, pos: Position) -> bool {
+        self.begin <= pos && pos < self.end
+    }
+
+    /// The byte range of the span within `file` - the form
+    ///     byte-oriented APIs (LSP, tree-sitter) expect.
+    pub fn byte_range(&self, file: &File) -> std::ops::Range<usize> {
+        file.to_byte(self.begin)..file.to_byte(self.end)
+    }
+
+    /// 0-based line of `begin` within `file`.
+    pub fn start_line(&self, file: &File) -> usize {
+        line_of(file, self.begin)
+    }
+
+    /// 0-based line of the last char within `file`. `end` is one
+    ///     past the span, so a span ending right after a newline
+    ///     doesn't leak onto the next line. Zero-width spans
+    ///     start and end on the same line.
+    pub fn end_line(&self, file: &File) -> usize {
+        match self.end > self.begin {
+            true => line_of(file, Position::new(self.end.as_usize() - 1).unwrap()),
+            false => self.start_line(file),
+        }
+    }
+
+    pub fn is_multiline(&self, file: &File) -> bool {
+        self.start_line(file) != self.end_line(file)
+    }
+
+    /// The covered line numbers, ready for iteration:
+    ///     `for line in span.line_range(&file)`.
+    pub fn line_range(&self, file: &File) -> std::ops::Range<usize> {
+        self.start_line(file)..self.end_line(file) + 1
+    }
+
+    /// `line:col-line:col` rendering of `self` within `file`
+    ///     (`line:col..col` when it fits one line), 1-based -
+    ///     see `Position::display`.
+    pub fn display<'f>(&self, file: &'f File) -> DisplaySpan<'f> {
+        DisplaySpan { span: *self, file }
+    }
+
+    /// The smallest span containing both, in any order -
+    ///     unlike `Add`, which requires ordered operands.
+    pub fn cover(a: Span, b: Span) -> Span {
+        Span {
+            begin: if a.begin <= b.begin { a.begin } else { b.begin },
+            end: if a.end >= b.end { a.end } else { b.end },
+            file: a.file,
+        }
+    }
+
+    pub fn join(self, other: Span) -> Span {
+        Self::cover(self, other)
+    }
+
+    /// Shifts the bounds lying at or after `from` by `delta`:
+    ///     keeps spans consistent across an in-place edit.
+    pub fn shift_from(&mut self, from: usize, delta: isize) {
+        if self.begin.as_usize() >= from {
+            self.begin.shift(delta)
+        }
+        if self.end.as_usize() >= from {
+            self.end.shift(delta)
+        }
+    }
+}
+
+fn line_of(file: &File, position: Position) -> usize {
+    file.line_starts
+        .partition_point(|&(_, c)| c <= position.as_usize())
+        - 1
 }
 
 impl Debug for Span {
@@ -113,6 +404,7 @@ impl Add for Span {
         Self {
             begin: self.begin,
             end: rhs.end,
+            file: self.file,
         }
     }
 }
@@ -132,3 +424,109 @@ macro_rules! implement_has_span {
     };
 }
 pub(crate) use implement_has_span;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn byte_char_conversion() {
+        let file = File::new_reader("fn f\n  привет 3\n".as_bytes()).unwrap();
+        let byte = file.code().find('3').unwrap();
+        let position = file.position_at(byte).unwrap();
+        assert_eq!(position.as_usize(), "fn f\n  привет ".chars().count());
+        assert_eq!(file.byte_offset(position), byte);
+        // Offsets inside a multi-byte char aren't positions.
+        assert!(file.position_at(8).is_none());
+    }
+
+    #[test]
+    fn byte_ranges() {
+        let file = File::new_reader("x привет y\n".as_bytes()).unwrap();
+        let begin = file.position_at(file.code().find('п').unwrap()).unwrap();
+        let span = Span::new(begin, begin.advanced("привет".chars().count()));
+        assert_eq!(file.code().get(span.byte_range(&file)), Some("привет"));
+        assert_eq!(file.to_byte(begin), 2);
+    }
+
+    #[test]
+    fn cover_is_associative() {
+        let span = |b, e| Span::new(Position::new(b).unwrap(), Position::new(e).unwrap());
+        let (a, b, c) = (span(4, 7), span(0, 2), span(5, 5));
+        assert_eq!(Span::cover(a, b), span(0, 7));
+        assert_eq!(
+            Span::cover(Span::cover(a, b), c),
+            Span::cover(a, Span::cover(b, c))
+        );
+        // Zero-width spans still extend the cover.
+        assert_eq!(Span::cover(b, c), span(0, 5));
+    }
+
+    #[test]
+    fn contains_position_boundaries() {
+        let position = |p| Position::new(p).unwrap();
+        // A span across several lines of "a\nbb\ncc\n".
+        let span = Span::new(position(2), position(7));
+        assert!(span.contains_position(position(2)));
+        assert!(span.contains_position(position(5)));
+        assert!(span.contains_position(position(6)));
+        // `end` is one past the last char - not inside.
+        assert!(!span.contains_position(position(7)));
+        assert!(!span.contains_position(position(1)));
+    }
+
+    #[test]
+    fn slice_by_span() {
+        let file = File::new_reader("f привет\ng x\n".as_bytes()).unwrap();
+        let position = |p| Position::new(p).unwrap();
+        let span = |b, e| Span::new(position(b), position(e));
+        assert_eq!(file.slice(&span(2, 8)), "привет");
+        // Multi-line spans keep the intervening newline.
+        assert_eq!(file.slice(&span(2, 10)), "привет\ng");
+        assert_eq!(file.slice(&span(3, 3)), "");
+        // Past the file there's nothing to slice.
+        assert_eq!(file.slice(&span(100, 200)), "");
+    }
+
+    #[test]
+    fn display_locations() {
+        let file = File::new_reader("a\nbb\ncc\n".as_bytes()).unwrap();
+        let position = |p| Position::new(p).unwrap();
+        assert_eq!(position(3).display(&file).to_string(), "2:2");
+        // Single-line spans shorten to `line:col..col`.
+        let span = Span::new(position(2), position(4));
+        assert_eq!(span.display(&file).to_string(), "2:1..3");
+        let span = Span::new(position(2), position(7));
+        assert_eq!(span.display(&file).to_string(), "2:1-3:3");
+        // Past the file there's no line to name.
+        assert_eq!(position(100).display(&file).to_string(), "@100");
+    }
+
+    #[test]
+    fn line_ranges() {
+        let file = File::new_reader("a\nbb\ncc\n".as_bytes()).unwrap();
+        let span = |b, e| Span::new(Position::new(b).unwrap(), Position::new(e).unwrap());
+        // "bb" sits alone on line 1.
+        let single = span(2, 4);
+        assert!(!single.is_multiline(&file));
+        assert_eq!(single.start_line(&file), 1);
+        assert_eq!(single.end_line(&file), 1);
+        assert_eq!(single.line_range(&file), 1..2);
+        // "a\nbb\ncc" covers all three lines.
+        let multi = span(0, 7);
+        assert!(multi.is_multiline(&file));
+        assert_eq!(multi.line_range(&file).collect::<Vec<_>>(), vec![0, 1, 2]);
+        // Ending right after the first newline stays on line 0.
+        assert_eq!(span(0, 2).end_line(&file), 0);
+        // A zero-width span covers exactly one line.
+        assert_eq!(span(5, 5).line_range(&file), 2..3);
+    }
+
+    #[test]
+    fn display_columns() {
+        let file = File::new_reader("日本 x\n".as_bytes()).unwrap();
+        let position = Position::new("日本 ".chars().count()).unwrap();
+        assert_eq!(position.get_line_and_offset(&file), Some((0, 3)));
+        assert_eq!(position.get_line_and_column(&file), Some((0, 5)));
+    }
+}
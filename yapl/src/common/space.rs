@@ -42,6 +42,10 @@ impl Id {
     pub fn as_u32(&self) -> u32 {
         self.id
     }
+
+    pub(crate) fn from_u32(id: u32) -> Self {
+        Self { id }
+    }
 }
 
 impl<T: std::cmp::PartialEq> Default for Space<T> {
@@ -5,14 +5,65 @@ use std::{
 
 use super::space::{Id, Space};
 
-static mut SYMBOLS: LazyLock<Mutex<Space<String>>> = LazyLock::new(|| Default::default());
+/// Identifier storage behind `Symbol`: identical strings must
+///     map to one id, so every spelling is allocated once however
+///     often it appears. The built-in process-wide interner is a
+///     `Space<String>`; `set_interner` installs a custom one
+///     (arena-backed, pre-seeded, ...) without touching callers.
+pub trait Interner: Send {
+    fn intern(&mut self, text: &str) -> u32;
+    fn resolve(&self, id: u32) -> &str;
+}
+
+impl Interner for Space<String> {
+    fn intern(&mut self, text: &str) -> u32 {
+        self.insert_unique(text.to_string()).as_u32()
+    }
+
+    fn resolve(&self, id: u32) -> &str {
+        self.get(Id::from_u32(id))
+    }
+}
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+static SYMBOLS: LazyLock<Mutex<Box<dyn Interner>>> =
+    LazyLock::new(|| Mutex::new(Box::new(Space::<String>::default())));
+
+/// Replaces the process-wide interner. Install it before any
+///     `Symbol` is created: existing handles index the old
+///     storage and aren't migrated.
+pub fn set_interner(interner: Box<dyn Interner>) {
+    *SYMBOLS.lock().unwrap() = interner
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Symbol(Id);
 
+impl Symbol {
+    /// Borrowed access to the spelling, without the copy
+    ///     `to_string` makes. The interner stays locked while `f`
+    ///     runs, so don't create symbols inside.
+    pub fn with_str<R>(&self, f: impl FnOnce(&str) -> R) -> R {
+        f(SYMBOLS.lock().unwrap().resolve(self.0.as_u32()))
+    }
+}
+
+// `Id` is only meaningful inside one process:
+//     serialized symbols carry their text and are re-interned on read.
+impl serde::Serialize for Symbol {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Symbol {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(String::deserialize(deserializer)?.into())
+    }
+}
+
 impl From<String> for Symbol {
     fn from(value: String) -> Self {
-        Self(unsafe { SYMBOLS.lock().unwrap().insert_unique(value) })
+        Self(Id::from_u32(SYMBOLS.lock().unwrap().intern(&value)))
     }
 }
 
@@ -25,7 +76,7 @@ impl From<&'static str> for Symbol {
 impl ToString for Symbol {
     // To be done: replace by as_str.
     fn to_string(&self) -> String {
-        unsafe { SYMBOLS.lock().unwrap().get(self.0).to_string() }
+        self.with_str(|s| s.to_string())
     }
 }
 
@@ -38,3 +89,32 @@ impl Debug for Symbol {
         ))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // A repeated identifier shares one handle - the memory win
+    //     the interner exists for.
+    #[test]
+    fn interning_shares_ids() {
+        let a = Symbol::from("very_long_identifier".to_string());
+        let b = Symbol::from("very_long_identifier".to_string());
+        assert_eq!(a, b);
+        a.with_str(|s| assert_eq!(s, "very_long_identifier"));
+        assert_eq!(a.to_string(), "very_long_identifier");
+    }
+
+    // A custom interner is exercised on its own - swapping the
+    //     global one would invalidate symbols of parallel tests.
+    #[test]
+    fn external_interner_contract() {
+        let mut interner = Space::<String>::default();
+        let a = interner.intern("abc");
+        let b = interner.intern("abc");
+        let c = interner.intern("abcd");
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(interner.resolve(a), "abc");
+    }
+}
@@ -0,0 +1,166 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use super::Obj;
+
+/// Data-flow dependencies between objects:
+///     edge `from -> to` means `to` is computed from `from`.
+#[derive(Default)]
+pub struct Graph {
+    nodes: Vec<Obj>,
+    index: HashMap<Obj, usize>,
+    successors: Vec<Vec<usize>>,
+}
+
+impl Graph {
+    pub fn add_node(&mut self, node: Obj) -> usize {
+        if let Some(&i) = self.index.get(&node) {
+            return i;
+        }
+        let i = self.nodes.len();
+        self.nodes.push(node);
+        self.index.insert(node, i);
+        self.successors.push(Vec::new());
+        i
+    }
+
+    pub fn add_edge(&mut self, from: Obj, to: Obj) {
+        let from = self.add_node(from);
+        let to = self.add_node(to);
+        self.successors[from].push(to)
+    }
+
+    /// All nodes participating in some cycle, or `None` if acyclic.
+    /// Iterative Tarjan - recursion depth doesn't depend on the graph.
+    pub fn detect_cycles(&self) -> Option<Vec<Obj>> {
+        let n = self.nodes.len();
+        const UNVISITED: usize = usize::MAX;
+        let mut index = vec![UNVISITED; n];
+        let mut low = vec![UNVISITED; n];
+        let mut on_stack = vec![false; n];
+        let mut stack = Vec::new();
+        let mut next_index = 0;
+        let mut cyclic = Vec::new();
+
+        for root in 0..n {
+            if index[root] != UNVISITED {
+                continue;
+            }
+            // Explicit call stack: (node, position in its successors).
+            let mut call = vec![(root, 0)];
+            while let Some(&(v, position)) = call.last() {
+                if position == 0 && index[v] == UNVISITED {
+                    index[v] = next_index;
+                    low[v] = next_index;
+                    next_index += 1;
+                    stack.push(v);
+                    on_stack[v] = true;
+                }
+                if let Some(&w) = self.successors[v].get(position) {
+                    call.last_mut().unwrap().1 += 1;
+                    if index[w] == UNVISITED {
+                        call.push((w, 0))
+                    } else if on_stack[w] {
+                        low[v] = low[v].min(index[w])
+                    }
+                    continue;
+                }
+                if low[v] == index[v] {
+                    let mut component = Vec::new();
+                    loop {
+                        let w = stack.pop().unwrap();
+                        on_stack[w] = false;
+                        component.push(w);
+                        if w == v {
+                            break;
+                        }
+                    }
+                    if component.len() > 1 || self.successors[v].contains(&v) {
+                        cyclic.extend(component)
+                    }
+                }
+                let finished = low[v];
+                call.pop();
+                if let Some(&(parent, _)) = call.last() {
+                    low[parent] = low[parent].min(finished)
+                }
+            }
+        }
+
+        if cyclic.is_empty() {
+            return None;
+        }
+        let mut cyclic: Vec<_> = cyclic.into_iter().map(|i| self.nodes[i]).collect();
+        cyclic.sort_by_key(|o| o.as_u32());
+        Some(cyclic)
+    }
+
+    /// Nodes in dependency order: every edge source precedes its target.
+    /// Deterministic - ties are broken by the smallest object id.
+    /// A cyclic graph yields the participating nodes as the error.
+    pub fn topo_order(&self) -> Result<Vec<Obj>, Vec<Obj>> {
+        if let Some(cycle) = self.detect_cycles() {
+            return Err(cycle);
+        }
+        let n = self.nodes.len();
+        let mut indegree = vec![0; n];
+        for successors in &self.successors {
+            for &to in successors {
+                indegree[to] += 1
+            }
+        }
+        let mut ready: BinaryHeap<_> = (0..n)
+            .filter(|&i| indegree[i] == 0)
+            .map(|i| Reverse((self.nodes[i].as_u32(), i)))
+            .collect();
+        let mut order = Vec::with_capacity(n);
+        while let Some(Reverse((_, from))) = ready.pop() {
+            order.push(self.nodes[from]);
+            for &to in &self.successors[from] {
+                indegree[to] -= 1;
+                if indegree[to] == 0 {
+                    ready.push(Reverse((self.nodes[to].as_u32(), to)))
+                }
+            }
+        }
+        Ok(order)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::space::Space;
+
+    fn objects(count: usize) -> Vec<Obj> {
+        let mut space: Space<usize> = Default::default();
+        (0..count).map(|i| space.insert(i)).collect()
+    }
+
+    #[test]
+    fn cycle_detection() {
+        let o = objects(4);
+        let mut graph = Graph::default();
+        graph.add_edge(o[0], o[1]);
+        graph.add_edge(o[1], o[2]);
+        graph.add_edge(o[2], o[3]);
+        assert!(graph.detect_cycles().is_none());
+
+        graph.add_edge(o[3], o[1]);
+        let cyclic = graph.detect_cycles().unwrap();
+        assert_eq!(cyclic, vec![o[1], o[2], o[3]]);
+    }
+
+    #[test]
+    fn topological_order() {
+        let o = objects(4);
+        let mut graph = Graph::default();
+        graph.add_edge(o[2], o[1]);
+        graph.add_edge(o[1], o[0]);
+        graph.add_edge(o[3], o[0]);
+        assert_eq!(graph.topo_order().unwrap(), vec![o[2], o[1], o[3], o[0]]);
+
+        graph.add_edge(o[0], o[2]);
+        assert!(graph.topo_order().is_err());
+    }
+}
@@ -1,5 +1,6 @@
 mod builtin;
 mod function;
+mod graph;
 mod set;
 mod r#trait;
 mod r#type;
@@ -7,6 +8,7 @@ mod r#type;
 use crate::common::space::Id as Obj;
 
 pub use function::Function;
+pub use graph::Graph;
 pub use r#trait::Trait;
 pub use r#type::{Instance, Type};
 pub use set::Set;
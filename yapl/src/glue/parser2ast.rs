@@ -5,7 +5,7 @@ use crate::common::symbol::Symbol;
 use crate::parser::ast as parser_ast;
 
 // To be done: don't stop on first error.
-use crate::common::error::{raise_error_on, Result};
+use crate::common::error::{raise_error_on, Error, Result};
 use crate::common::location::HasSpan;
 
 use super::errors::ToBeDone;
@@ -14,6 +14,32 @@ pub fn parser2ast(parser: &parser_ast::File) -> Result<Vec<ast::Line>> {
     parser.roots().iter().map(p2a_line).collect()
 }
 
+/// Error-tolerant variant of `parser2ast`: a line that fails to
+///     convert is dropped together with its block - conversion
+///     resumes at the next line at equal or lesser indentation -
+///     and the error is collected instead of aborting. Surviving
+///     regions are complete, valid sub-trees.
+pub fn parser2ast_with_recovery(parser: &parser_ast::File) -> (Vec<ast::Line>, Vec<Error>) {
+    let mut errors = Vec::new();
+    let lines = p2a_lines_recover(parser.roots(), &mut errors);
+    (lines, errors)
+}
+
+fn p2a_lines_recover(lines: &[parser_ast::Line], errors: &mut Vec<Error>) -> Vec<ast::Line> {
+    let mut result = Vec::new();
+    for line in lines {
+        match p2a_sent(&line.sent) {
+            Ok(sent) => {
+                let extension = p2a_lines_recover(&line.extension, errors);
+                let block = p2a_lines_recover(&line.block, errors);
+                result.push(ast::Line::new(sent, extension, block, line.span))
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+    result
+}
+
 fn p2a_line(line: &parser_ast::Line) -> Result<ast::Line> {
     let block: Result<Vec<_>> = line.block.iter().map(p2a_line).collect();
     let extension: Result<Vec<_>> = line.extension.iter().map(p2a_line).collect();
@@ -22,8 +48,123 @@ fn p2a_line(line: &parser_ast::Line) -> Result<ast::Line> {
 }
 
 fn p2a_sent(sent: &parser_ast::Sent) -> Result<ast::NodeS> {
-    let phrase: Result<Vec<_>> = sent.sent.iter().map(p2a_expr).collect();
-    phrase.map(|p| ast::NodeS::new_p(p, sent.span))
+    // Comments have no semantic meaning.
+    let phrase: Result<Vec<_>> = sent
+        .sent
+        .iter()
+        .filter(|e| {
+            !matches!(
+                e.expr,
+                parser_ast::ExprT::DocComment(_) | parser_ast::ExprT::Comment(_)
+            )
+        })
+        .map(p2a_expr)
+        .collect();
+    phrase.map(|p| ast::NodeS::new_p(fuse_annotations(fuse_calls(p)), sent.span))
+}
+
+// `f(x, y)` is a call and `a[0]` an indexing; `f (x, y)` stays
+//     juxtaposition. The spans decide: fused only with no gap
+//     between base and bracket. Fused nodes keep fusing, so
+//     `a[0][1]` and `f(x)(y)` nest left to right.
+fn fuse_calls(phrase: Vec<ast::NodeS>) -> Vec<ast::NodeS> {
+    fn fusable(node: &ast::NodeS) -> bool {
+        matches!(
+            node.node(),
+            ast::Node::Chain(_) | ast::Node::Keyword(_) | ast::Node::Call(..) | ast::Node::Index(..)
+        )
+    }
+    let mut result: Vec<ast::NodeS> = Vec::new();
+    for node in phrase {
+        let bracket = match (result.last(), node.node()) {
+            (Some(prev), ast::Node::Bracket(bracket, _))
+                if fusable(prev) && prev.span().end() == node.span().begin() =>
+            {
+                Some(*bracket)
+            }
+            _ => None,
+        };
+        let constructor = match bracket {
+            Some(ast::Bracket::Round) => ast::NodeS::new_call,
+            Some(ast::Bracket::Square) => ast::NodeS::new_index,
+            _ => {
+                result.push(node);
+                continue;
+            }
+        };
+        let base = result.pop().unwrap();
+        let parts = match node.node() {
+            ast::Node::Bracket(_, parts) => parts.clone(),
+            _ => unreachable!(),
+        };
+        let span = base.span() + node.span();
+        result.push(constructor(base, parts, span))
+    }
+    result
+}
+
+// `@name` is an annotation, `@ name` two separate nodes - the
+//     spans decide, as with calls. `fuse_calls` runs first, so
+//     `@route("/x")` arrives as `@` plus a call and the arguments
+//     carry over; a bare `@name` gets none.
+fn fuse_annotations(phrase: Vec<ast::NodeS>) -> Vec<ast::NodeS> {
+    fn marker(node: &ast::NodeS) -> bool {
+        matches!(node.node(), ast::Node::Chain(c) if *c == ["@".into()])
+    }
+    let mut result: Vec<ast::NodeS> = Vec::new();
+    for node in phrase {
+        let fuse = match result.last() {
+            Some(prev) if marker(prev) && prev.span().end() == node.span().begin() => {
+                matches!(node.node(), ast::Node::Chain(_) | ast::Node::Call(..))
+            }
+            _ => false,
+        };
+        if !fuse {
+            result.push(node);
+            continue;
+        }
+        let at = result.pop().unwrap();
+        let span = at.span() + node.span();
+        let (name, args) = match node.node().clone() {
+            ast::Node::Call(callee, args) => (*callee, args),
+            other => (ast::NodeS::new(other, node.span()), Vec::new()),
+        };
+        result.push(ast::NodeS::new_annotation(name, args, span))
+    }
+    result
+}
+
+/// Lowers the generic brackets left standing after call fusing
+///     into semantically named nodes:
+///     - `(a)` with a single part becomes `Node::Group`;
+///     - any other `(...)`, including `()`, becomes `Node::Tuple`;
+///     - `[...]` becomes `Node::List`;
+///     - `{...}` becomes `Node::Set` - blocks come from
+///       indentation, so inline `{}` is always a collection.
+/// An opt-in second pass: `parser2ast` itself keeps
+///     `Node::Bracket`, so consumers matching on the raw kind
+///     are unaffected.
+pub fn lower_brackets(lines: &mut [ast::Line]) {
+    struct Lower;
+    impl crate::ast::VisitorMut for Lower {
+        fn visit_node(&mut self, node: &mut ast::NodeS) {
+            crate::ast::visit::walk_node_mut(self, node);
+            let placeholder = ast::Node::Phrase(Vec::new());
+            let lowered = match std::mem::replace(node.node_mut(), placeholder) {
+                ast::Node::Bracket(ast::Bracket::Round, mut parts) if parts.len() == 1 => {
+                    ast::Node::Group(Box::new(parts.pop().unwrap()))
+                }
+                ast::Node::Bracket(ast::Bracket::Round, parts) => ast::Node::Tuple(parts),
+                ast::Node::Bracket(ast::Bracket::Square, parts) => ast::Node::List(parts),
+                ast::Node::Bracket(ast::Bracket::Curly, parts) => ast::Node::Set(parts),
+                other => other,
+            };
+            *node.node_mut() = lowered;
+        }
+    }
+    for line in lines {
+        crate::ast::VisitorMut::visit_line(&mut Lower, line)
+    }
 }
 
 // To be done: make code at least a little better...
@@ -35,8 +176,17 @@ fn p2a_expr(expr: &parser_ast::Expr) -> Result<ast::NodeS> {
         },
         parser_ast::ExprT::Chain(c) => ast::NodeS::new_c(c.to_vec(), expr.span),
         parser_ast::ExprT::Special(s) => ast::NodeS::new_c(vec![s.clone()], expr.span),
+        parser_ast::ExprT::Keyword(k) => ast::NodeS::new_k(*k, expr.span),
         parser_ast::ExprT::LitStr(s) => ast::NodeS::new_ls(s.clone(), expr.span),
-        parser_ast::ExprT::LitInt(i) => ast::NodeS::new_li(*i, expr.span),
+        parser_ast::ExprT::LitInt(i, _, _) => ast::NodeS::new_li(*i, expr.span),
+        parser_ast::ExprT::LitFloat(f, _) => ast::NodeS::new_lf(*f, expr.span),
+        parser_ast::ExprT::LitChar(c) => ast::NodeS::new_lc(*c, expr.span),
+        parser_ast::ExprT::LitBool(b) => ast::NodeS::new_lb(*b, expr.span),
+        // Comments are filtered out in `p2a_sent`.
+        parser_ast::ExprT::DocComment(_) | parser_ast::ExprT::Comment(_) => {
+            raise_error_on!(ToBeDone, expr,)
+        }
+        parser_ast::ExprT::Error => raise_error_on!(ToBeDone, expr,),
         parser_ast::ExprT::Bracket(bt, sentences) => {
             let sentences: Result<_> = sentences.iter().map(p2a_sent).collect();
             match bt {
@@ -51,3 +201,188 @@ fn p2a_expr(expr: &parser_ast::Expr) -> Result<ast::NodeS> {
 fn p2a_dot_chain(chain: &Vec<Symbol>) -> Vec<Symbol> {
     vec![".".into()].into_iter().chain(chain.clone()).collect()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn convert(src: &str) -> Vec<ast::Line> {
+        let file = crate::common::location::File::new_str("".into(), src).unwrap();
+        let parsed = crate::parser::parse(&file).unwrap();
+        parser2ast(&parsed).unwrap()
+    }
+
+    #[test]
+    fn call_vs_juxtaposition() {
+        let lines = convert("f(x, y)\n");
+        let phrase = match lines[0].line().node() {
+            ast::Node::Phrase(phrase) => phrase,
+            other => panic!("not a phrase: {:?}", other),
+        };
+        assert_eq!(phrase.len(), 1);
+        match phrase[0].node() {
+            ast::Node::Call(callee, args) => {
+                assert!(matches!(callee.node(), ast::Node::Chain(_)));
+                assert_eq!(args.len(), 2);
+            }
+            other => panic!("not a call: {:?}", other),
+        }
+
+        // A space before the bracket keeps them separate.
+        let lines = convert("f (x)\n");
+        let phrase = match lines[0].line().node() {
+            ast::Node::Phrase(phrase) => phrase,
+            other => panic!("not a phrase: {:?}", other),
+        };
+        assert_eq!(phrase.len(), 2);
+        assert!(matches!(phrase[1].node(), ast::Node::Bracket(..)));
+    }
+
+    // All three bracket kinds survive the conversion, so a list
+    //     `[]` and a block `{}` stay distinguishable downstream.
+    #[test]
+    fn bracket_kinds_survive() {
+        let lines = convert("f (a) [b] {c}\n");
+        let phrase = match lines[0].line().node() {
+            ast::Node::Phrase(phrase) => phrase,
+            other => panic!("not a phrase: {:?}", other),
+        };
+        let kind = |node: &ast::NodeS| match node.node() {
+            ast::Node::Bracket(bracket, _) => *bracket,
+            other => panic!("not a bracket: {:?}", other),
+        };
+        assert!(matches!(kind(&phrase[1]), ast::Bracket::Round));
+        assert!(matches!(kind(&phrase[2]), ast::Bracket::Square));
+        assert!(matches!(kind(&phrase[3]), ast::Bracket::Curly));
+    }
+
+    // `ExprT::Error` only survives the line parser in
+    //     `collect_errors` mode, so the tree is built by hand:
+    //     a good line, a garbage line carrying a block, another
+    //     good line.
+    #[test]
+    fn recovery_at_indent_boundaries() {
+        use crate::common::location::{File, Position, Span};
+        let span = |b, e| Span::new(Position::new(b).unwrap(), Position::new(e).unwrap());
+        let chain = |b: usize, e: usize| {
+            let expr = parser_ast::Expr::new_c(vec!["a".into()], span(b, e));
+            parser_ast::Line::new(parser_ast::Sent::new(vec![expr]).unwrap())
+        };
+        let mut garbage = parser_ast::Line::new(
+            parser_ast::Sent::new(vec![parser_ast::Expr::new_e(span(4, 7))]).unwrap(),
+        );
+        garbage.block.push(chain(10, 15));
+        let file = File::new_str("".into(), "a x\n, ,\n  child\nb y\n").unwrap();
+        let roots = vec![chain(0, 1), garbage, chain(16, 17)];
+        let parsed = parser_ast::File::new(&file, roots, file.span());
+        // The strict conversion gives up on the garbage line.
+        assert!(parser2ast(&parsed).is_err());
+        // Recovery drops it with its block and keeps the siblings.
+        let (lines, errors) = parser2ast_with_recovery(&parsed);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(lines[0].span().begin().as_usize(), 0);
+        assert!(lines.iter().all(|l| l.block().is_empty()));
+    }
+
+    #[test]
+    fn bracket_lowering() {
+        let lower = |src: &str| {
+            let mut lines = convert(src);
+            lower_brackets(&mut lines);
+            lines
+        };
+        let first = |lines: &[ast::Line]| match lines[0].line().node() {
+            ast::Node::Phrase(phrase) => phrase[1].node().clone(),
+            other => panic!("not a phrase: {:?}", other),
+        };
+        // A single round part is grouping, several are a tuple.
+        assert!(matches!(first(&lower("f (a)\n")), ast::Node::Group(_)));
+        let node = first(&lower("f (a, b)\n"));
+        assert!(matches!(node, ast::Node::Tuple(ref parts) if parts.len() == 2));
+        assert!(matches!(first(&lower("f ()\n")), ast::Node::Tuple(_)));
+        assert!(matches!(first(&lower("f [a, b]\n")), ast::Node::List(_)));
+        assert!(matches!(first(&lower("f {a}\n")), ast::Node::Set(_)));
+        // Nested brackets are lowered too.
+        let node = first(&lower("f [(a), b]\n"));
+        let parts = match node {
+            ast::Node::List(ref parts) => parts,
+            other => panic!("not a list: {:?}", other),
+        };
+        // Each part is a phrase, the group sits inside it.
+        let part = match parts[0].node() {
+            ast::Node::Phrase(sub) => sub[0].node(),
+            other => panic!("not a phrase: {:?}", other),
+        };
+        assert!(matches!(part, ast::Node::Group(_)));
+        // Fused calls keep their arguments, not a `Tuple`.
+        let lines = lower("f(a, b)\n");
+        let phrase = match lines[0].line().node() {
+            ast::Node::Phrase(phrase) => phrase,
+            other => panic!("not a phrase: {:?}", other),
+        };
+        assert!(matches!(phrase[0].node(), ast::Node::Call(..)));
+    }
+
+    #[test]
+    fn annotations() {
+        let phrase_of = |lines: &[ast::Line]| match lines[0].line().node() {
+            ast::Node::Phrase(phrase) => phrase.clone(),
+            other => panic!("not a phrase: {:?}", other),
+        };
+        // A standalone `@test` annotates its indented block.
+        let lines = convert("@test\n  f x\n");
+        let phrase = phrase_of(&lines);
+        assert_eq!(phrase.len(), 1);
+        match phrase[0].node() {
+            ast::Node::Annotation(name, args) => {
+                assert!(matches!(name.node(), ast::Node::Chain(_)));
+                assert!(args.is_empty());
+            }
+            other => panic!("not an annotation: {:?}", other),
+        }
+        assert_eq!(lines[0].block().len(), 1);
+        // `@route("/x")` keeps the call arguments and precedes the
+        //     annotated statement on the same line.
+        let lines = convert("@route(\"/x\") f x\n");
+        let phrase = phrase_of(&lines);
+        assert_eq!(phrase.len(), 3);
+        match phrase[0].node() {
+            ast::Node::Annotation(name, args) => {
+                assert!(matches!(name.node(), ast::Node::Chain(_)));
+                assert_eq!(args.len(), 1);
+            }
+            other => panic!("not an annotation: {:?}", other),
+        }
+        assert_eq!(phrase[0].span().begin().as_usize(), 0);
+        assert_eq!(phrase[0].span().end().as_usize(), 12);
+        // A space after `@` keeps it a plain special chain.
+        let phrase = phrase_of(&convert("@ f\n"));
+        assert_eq!(phrase.len(), 2);
+        assert!(matches!(phrase[0].node(), ast::Node::Chain(_)));
+    }
+
+    #[test]
+    fn nested_indexing() {
+        let lines = convert("a[0][1]\n");
+        let phrase = match lines[0].line().node() {
+            ast::Node::Phrase(phrase) => phrase,
+            other => panic!("not a phrase: {:?}", other),
+        };
+        assert_eq!(phrase.len(), 1);
+        let (base, index) = match phrase[0].node() {
+            ast::Node::Index(base, index) => (base, index),
+            other => panic!("not an indexing: {:?}", other),
+        };
+        assert_eq!(index.len(), 1);
+        // The outer span reaches the closing bracket.
+        assert_eq!(phrase[0].span().begin().as_usize(), 0);
+        assert_eq!(phrase[0].span().end().as_usize(), 7);
+        match base.node() {
+            ast::Node::Index(inner, _) => {
+                assert!(matches!(inner.node(), ast::Node::Chain(_)))
+            }
+            other => panic!("base isn't an indexing: {:?}", other),
+        }
+    }
+}
@@ -11,12 +11,37 @@ mod dfg;
 mod parser;
 
 /// `location::Context as File` -> Result<parser::Ast>.
-pub use parser::parse;
+pub use parser::{parse, parse_reader, parse_str, parse_str_named, parse_with_config};
+pub use parser::parse_with_warnings;
+pub use parser::{parse_incomplete, ParseStatus};
+pub use parser::{parse_lines, ParseLines};
+pub use parser::detect_indent;
+pub use parser::{line_kinds, LineKind};
+pub use parser::{ParseConfig, Parsed};
+pub use parser::{tokenize, Lexer, Token};
+pub use parser::{highlight, highlight_with_config, TokenClass};
+pub use parser::{SymbolType, OPERATORS};
+
+/// parser::Ast -> canonically formatted source.
+pub use parser::print;
+
+/// Untyped tree produced by `parse`, for tooling that inspects
+///     lines and expressions directly.
+pub use parser::ast as parser_ast;
+pub use parser::ast::{AstStats, BracketType, Radix};
 
 /// parser::Ast -> ast::Ast.
 pub use glue::parser2ast::parser2ast;
+pub use glue::parser2ast::lower_brackets;
+pub use glue::parser2ast::parser2ast_with_recovery;
 
-pub use common::error::Result;
-pub use common::location::File;
+pub use common::error::{diagnostics_to_json, ColumnMode, ErrorKind, Result, Severity};
+#[cfg(feature = "miette")]
+pub use common::error::Diagnostic;
+pub use common::location::{DisplayPosition, DisplaySpan, File, FileId, HasSpan, Position, Span};
+pub use common::symbol::{set_interner, Interner, Symbol};
 
 pub use ast::Project;
+pub use ast::{MemoryCache, ParseCache};
+pub use ast::{build_expr_tree, fold_constants, Operator};
+pub use ast::{Visitor, VisitorMut};
@@ -1,7 +1,7 @@
 // To be done: is pub necessary?
-pub use super::symbol::BracketType;
+pub use super::symbol::{BracketType, Radix};
 
-use crate::common::location::{self, implement_has_span, Span};
+use crate::common::location::{self, implement_has_span, HasSpan, Position, Span};
 use crate::common::symbol::Symbol;
 
 use serde::{Deserialize, Serialize};
@@ -18,34 +18,347 @@ pub struct File<'file> {
 
 // To be done: write template ast to not copy.
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+impl File<'_> {
+    /// Depth-first walk over every line, root lines first,
+    ///     yielding the nesting depth alongside each line.
+    pub fn lines(&self) -> Lines<'_> {
+        Lines::new(&self.roots)
+    }
+
+    /// The chain of nodes from a root line down to the innermost
+    ///     node covering `pos` - hover and go-to-definition ask
+    ///     exactly this. `None` when no line covers `pos`.
+    pub fn node_at(&self, pos: Position) -> Option<Vec<PathNode<'_>>> {
+        node_at(&self.roots, pos)
+    }
+}
+
+/// Stable indented dump of the whole tree - node kinds, literal
+///     values and spans - meant for golden/snapshot tests, where
+///     a span regression should show up as a readable diff.
+pub fn debug_tree(file: &File) -> String {
+    debug_roots(file.roots())
+}
+
+pub(crate) fn debug_roots(roots: &[Line]) -> String {
+    let mut out = String::new();
+    for line in roots {
+        debug_line(&mut out, line, 0, "line");
+    }
+    out
+}
+
+fn debug_line(out: &mut String, line: &Line, indent: usize, label: &str) {
+    let pad = "  ".repeat(indent);
+    out.push_str(&format!("{}{} {:?}\n", pad, label, line.span));
+    debug_sent(out, &line.sent, indent + 1);
+    for sub in &line.extension {
+        debug_line(out, sub, indent + 1, "ext line")
+    }
+    for sub in &line.block {
+        debug_line(out, sub, indent + 1, "block line")
+    }
+}
+
+fn debug_sent(out: &mut String, sent: &Sent, indent: usize) {
+    let pad = "  ".repeat(indent);
+    out.push_str(&format!("{}sent {:?}\n", pad, sent.span));
+    for expr in &sent.sent {
+        debug_expr(out, expr, indent + 1)
+    }
+}
+
+fn debug_expr(out: &mut String, expr: &Expr, indent: usize) {
+    let pad = "  ".repeat(indent);
+    match &expr.expr {
+        ExprT::Inner(inner) => {
+            out.push_str(&format!("{}inner {:?}\n", pad, expr.span));
+            debug_expr(out, inner, indent + 1)
+        }
+        ExprT::Special(s) => {
+            out.push_str(&format!("{}special {} {:?}\n", pad, s.to_string(), expr.span))
+        }
+        ExprT::Keyword(k) => {
+            out.push_str(&format!("{}keyword {} {:?}\n", pad, k.to_string(), expr.span))
+        }
+        ExprT::Chain(chain) => {
+            let chain: Vec<_> = chain.iter().map(|s| s.to_string()).collect();
+            out.push_str(&format!("{}chain {} {:?}\n", pad, chain.join("."), expr.span))
+        }
+        ExprT::Bracket(bt, parts) => {
+            out.push_str(&format!("{}bracket {:?} {:?}\n", pad, bt, expr.span));
+            for sent in parts {
+                debug_sent(out, sent, indent + 1)
+            }
+        }
+        ExprT::LitStr(s) => out.push_str(&format!("{}str {:?} {:?}\n", pad, s, expr.span)),
+        ExprT::LitInt(i, radix, suffix) => {
+            let suffix = suffix.as_ref().map(|s| s.to_string()).unwrap_or_default();
+            out.push_str(&format!("{}int {}{} {:?} {:?}\n", pad, i, suffix, radix, expr.span))
+        }
+        ExprT::LitFloat(f, suffix) => {
+            let suffix = suffix.as_ref().map(|s| s.to_string()).unwrap_or_default();
+            out.push_str(&format!("{}float {}{} {:?}\n", pad, f, suffix, expr.span))
+        }
+        ExprT::LitChar(c) => out.push_str(&format!("{}char {:?} {:?}\n", pad, c, expr.span)),
+        ExprT::LitBool(b) => out.push_str(&format!("{}bool {} {:?}\n", pad, b, expr.span)),
+        ExprT::DocComment(text) => {
+            out.push_str(&format!("{}doc-comment {:?} {:?}\n", pad, text, expr.span))
+        }
+        ExprT::Comment(text) => {
+            out.push_str(&format!("{}comment {:?} {:?}\n", pad, text, expr.span))
+        }
+        ExprT::Error => out.push_str(&format!("{}error {:?}\n", pad, expr.span)),
+    }
+}
+
+/// A foldable region: 0-based first and last source lines of a
+///     line together with all of its sub lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FoldRange {
+    pub begin: usize,
+    pub end: usize,
+}
+
+impl File<'_> {
+    /// Fold regions for editors: one range per line with sub
+    ///     lines, parents before their nested ranges. Blank and
+    ///     comment lines inside a block fold with it - the ranges
+    ///     come from spans, which cover everything in between.
+    pub fn folding_ranges(&self) -> Vec<FoldRange> {
+        folding_ranges(&self.roots, self.context)
+    }
+}
+
+pub(crate) fn folding_ranges(roots: &[Line], file: &location::File) -> Vec<FoldRange> {
+    let mut result = Vec::new();
+    for line in roots {
+        fold_line(line, file, &mut result)
+    }
+    result
+}
+
+fn fold_line(line: &Line, file: &location::File, result: &mut Vec<FoldRange>) {
+    if line.children().next().is_some() {
+        // The span end is exclusive: step one char back to stay
+        //     on the last covered line.
+        let last = Position::new(line.span.end().as_usize().saturating_sub(1)).unwrap();
+        if let (Some((begin, _)), Some((end, _))) = (
+            line.span.begin().get_line_and_offset(file),
+            last.get_line_and_offset(file),
+        ) {
+            if end > begin {
+                result.push(FoldRange { begin, end })
+            }
+        }
+    }
+    for sub in line.children() {
+        fold_line(sub, file, result)
+    }
+}
+
+/// Cheap per-parse metrics for build reporting -
+///     see `File::stats`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AstStats {
+    /// Every line, nested ones included.
+    pub lines: usize,
+    /// Line sentences plus bracket parts.
+    pub sentences: usize,
+    pub chains: usize,
+    pub ints: usize,
+    pub floats: usize,
+    pub strings: usize,
+    pub brackets: usize,
+    /// The deepest point, counting both indentation and bracket
+    ///     nesting; an empty file stays at zero.
+    pub max_depth: usize,
+}
+
+impl File<'_> {
+    /// Counts of the parsed structure, one tree walk.
+    pub fn stats(&self) -> AstStats {
+        stats(&self.roots)
+    }
+}
+
+pub(crate) fn stats(roots: &[Line]) -> AstStats {
+    let mut stats = AstStats::default();
+    for line in roots {
+        stat_line(line, 1, &mut stats)
+    }
+    stats
+}
+
+fn stat_line(line: &Line, depth: usize, stats: &mut AstStats) {
+    stats.lines += 1;
+    stats.max_depth = stats.max_depth.max(depth);
+    stat_sent(&line.sent, depth, stats);
+    for sub in line.children() {
+        stat_line(sub, depth + 1, stats)
+    }
+}
+
+fn stat_sent(sent: &Sent, depth: usize, stats: &mut AstStats) {
+    stats.sentences += 1;
+    for expr in &sent.sent {
+        stat_expr(expr, depth, stats)
+    }
+}
+
+fn stat_expr(expr: &Expr, depth: usize, stats: &mut AstStats) {
+    match &expr.expr {
+        ExprT::Inner(inner) => stat_expr(inner, depth, stats),
+        ExprT::Chain(_) => stats.chains += 1,
+        ExprT::LitInt(..) => stats.ints += 1,
+        ExprT::LitFloat(..) => stats.floats += 1,
+        ExprT::LitStr(_) => stats.strings += 1,
+        ExprT::Bracket(_, parts) => {
+            stats.brackets += 1;
+            stats.max_depth = stats.max_depth.max(depth + 1);
+            for part in parts {
+                stat_sent(part, depth + 1, stats)
+            }
+        }
+        _ => {}
+    }
+}
+
+/// One step of a `node_at` path, innermost last.
+#[derive(Debug, Clone, Copy)]
+pub enum PathNode<'ast> {
+    Line(&'ast Line),
+    Sent(&'ast Sent),
+    Expr(&'ast Expr),
+}
+
+impl HasSpan for PathNode<'_> {
+    fn span(&self) -> Span {
+        match self {
+            Self::Line(line) => line.span,
+            Self::Sent(sent) => sent.span,
+            Self::Expr(expr) => expr.span,
+        }
+    }
+}
+
+pub(crate) fn node_at(roots: &[Line], pos: Position) -> Option<Vec<PathNode<'_>>> {
+    let line = roots.iter().find(|l| l.span.contains_position(pos))?;
+    let mut path = Vec::new();
+    line_at(line, pos, &mut path);
+    Some(path)
+}
+
+fn line_at<'ast>(line: &'ast Line, pos: Position, path: &mut Vec<PathNode<'ast>>) {
+    path.push(PathNode::Line(line));
+    if line.sent.span.contains_position(pos) {
+        return sent_at(&line.sent, pos, path);
+    }
+    for sub in line.children() {
+        if sub.span.contains_position(pos) {
+            return line_at(sub, pos, path);
+        }
+    }
+}
+
+fn sent_at<'ast>(sent: &'ast Sent, pos: Position, path: &mut Vec<PathNode<'ast>>) {
+    path.push(PathNode::Sent(sent));
+    for expr in &sent.sent {
+        if expr.span.contains_position(pos) {
+            return expr_at(expr, pos, path);
+        }
+    }
+}
+
+fn expr_at<'ast>(expr: &'ast Expr, pos: Position, path: &mut Vec<PathNode<'ast>>) {
+    path.push(PathNode::Expr(expr));
+    match &expr.expr {
+        ExprT::Inner(inner) if inner.span.contains_position(pos) => expr_at(inner, pos, path),
+        ExprT::Bracket(_, parts) => {
+            for sent in parts {
+                if sent.span.contains_position(pos) {
+                    return sent_at(sent, pos, path);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Iterator of `File::lines`: `(depth, line)` in document order.
+pub struct Lines<'ast> {
+    stack: Vec<(usize, &'ast Line)>,
+}
+
+impl<'ast> Lines<'ast> {
+    pub(crate) fn new(roots: &'ast [Line]) -> Self {
+        Self {
+            stack: roots.iter().rev().map(|line| (0, line)).collect(),
+        }
+    }
+}
+
+impl<'ast> Iterator for Lines<'ast> {
+    type Item = (usize, &'ast Line);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (depth, line) = self.stack.pop()?;
+        self.stack
+            .extend(line.children().rev().map(|sub| (depth + 1, sub)));
+        Some((depth, line))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Line {
     pub sent: Sent,
     pub extension: Vec<Line>,
     pub block: Vec<Line>,
     pub span: Span, // Starts from `sent`. Contains all sub lines.
+    /// Indentation level as written: leading spaces divided by
+    ///     `ParseConfig::indent_width`, not nesting depth (an
+    ///     extension line sits three levels below its parent).
+    #[serde(default)]
+    pub offset: usize,
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Sent {
     pub sent: Vec<Expr>,
     pub span: Span,
 }
 
-#[derive(Debug, PartialEq, derive_new::new, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, derive_new::new, Serialize, Deserialize)]
 pub struct Expr {
     pub expr: ExprT,
     pub span: Span,
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ExprT {
     Inner(Box<Expr>),
     Special(Symbol),
+    /// A word from `ParseConfig::keywords`, reserved - never
+    ///     an identifier.
+    Keyword(Symbol),
     Chain(Vec<Symbol>),
     Bracket(BracketType, Vec<Sent>),
     LitStr(String),
-    LitInt(i64),
+    /// The optional `Symbol` is a typed suffix glued to the literal: `5u8`.
+    LitInt(i64, Radix, Option<Symbol>),
+    LitFloat(f64, Option<Symbol>),
+    LitChar(char),
+    /// `true`/`false`, produced only when they are listed in
+    ///     `ParseConfig::keywords`.
+    LitBool(bool),
+    /// ".." comment: retained for documentation tooling,
+    ///     unlike ". " comments which are dropped.
+    DocComment(String),
+    /// ". " comment: retained only with `ParseConfig::keep_comments`.
+    Comment(String),
+    /// Placeholder kept in error-accumulation mode
+    ///     where an expression failed to parse.
+    Error,
 }
 
 implement_has_span!(Expr, Sent, Line);
@@ -58,9 +371,15 @@ impl Line {
             extension: Default::default(),
             block: Default::default(),
             span,
+            offset: 0,
         }
     }
 
+    /// The indentation level, for tools folding on indentation.
+    pub fn indent(&self) -> usize {
+        self.offset
+    }
+
     pub fn update(&mut self, extension: Vec<Line>, block: Vec<Line>) {
         self.extension = extension;
         self.block = block;
@@ -69,9 +388,67 @@ impl Line {
             (None, None) => self.span,
         }
     }
+
+    /// Comparison ignoring spans, so structurally identical trees
+    ///     from different source positions compare equal - for
+    ///     tests and subtree deduplication. Indentation offsets
+    ///     still count; `PartialEq` compares spans too.
+    pub fn structural_eq(&self, other: &Self) -> bool {
+        let all = |a: &[Line], b: &[Line]| {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.structural_eq(b))
+        };
+        self.offset == other.offset
+            && self.sent.structural_eq(&other.sent)
+            && all(&self.extension, &other.extension)
+            && all(&self.block, &other.block)
+    }
+
+    /// The sub lines, extension before block - the order they
+    ///     appear in the source.
+    pub fn children(&self) -> impl DoubleEndedIterator<Item = &Line> {
+        self.extension.iter().chain(self.block.iter())
+    }
+
+    /// Shifts every position at or after `from` by `delta`,
+    ///     so later spans survive an in-place edit of one line.
+    pub(crate) fn shift_positions(&mut self, from: usize, delta: isize) {
+        self.span.shift_from(from, delta);
+        self.sent.shift_positions(from, delta);
+        for line in self.extension.iter_mut().chain(self.block.iter_mut()) {
+            line.shift_positions(from, delta)
+        }
+    }
+
+    /// Recomputes `span` from `sent` and the sub lines, bottom-up.
+    pub(crate) fn refresh_span(&mut self) {
+        for line in self.extension.iter_mut().chain(self.block.iter_mut()) {
+            line.refresh_span()
+        }
+        self.span = match (self.extension.last(), self.block.last()) {
+            (Some(last), None) | (_, Some(last)) => self.sent.span + last.span,
+            (None, None) => self.sent.span,
+        }
+    }
 }
 
 impl Sent {
+    pub(crate) fn shift_positions(&mut self, from: usize, delta: isize) {
+        self.span.shift_from(from, delta);
+        for expr in &mut self.sent {
+            expr.shift_positions(from, delta)
+        }
+    }
+
+    /// Span-insensitive comparison - see `Line::structural_eq`.
+    pub fn structural_eq(&self, other: &Self) -> bool {
+        self.sent.len() == other.sent.len()
+            && self
+                .sent
+                .iter()
+                .zip(&other.sent)
+                .all(|(a, b)| a.structural_eq(b))
+    }
+
     pub fn new(sent: Vec<Expr>) -> Option<Self> {
         if let (Some(first), Some(last)) = (sent.first(), sent.last()) {
             let span = first.span + last.span;
@@ -92,7 +469,46 @@ macro_rules! expr_new {
 }
 expr_new!(new_i, Inner, inner: Box<Expr>);
 expr_new!(new_s, Special, special: Symbol);
+expr_new!(new_k, Keyword, keyword: Symbol);
 expr_new!(new_c, Chain, chain: Vec<Symbol>);
 expr_new!(new_b, Bracket, ty: BracketType, parts: Vec<Sent>);
 expr_new!(new_ls, LitStr, val: String);
-expr_new!(new_li, LitInt, val: i64);
+expr_new!(new_li, LitInt, val: i64, radix: Radix, suffix: Option<Symbol>);
+expr_new!(new_lf, LitFloat, val: f64, suffix: Option<Symbol>);
+expr_new!(new_lc, LitChar, val: char);
+expr_new!(new_lb, LitBool, val: bool);
+expr_new!(new_dc, DocComment, text: String);
+expr_new!(new_cm, Comment, text: String);
+
+impl Expr {
+    pub fn new_e(span: Span) -> Self {
+        Self::new(ExprT::Error, span)
+    }
+
+    /// Span-insensitive comparison - see `Line::structural_eq`.
+    pub fn structural_eq(&self, other: &Self) -> bool {
+        let all = |a: &[Sent], b: &[Sent]| {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.structural_eq(b))
+        };
+        match (&self.expr, &other.expr) {
+            (ExprT::Inner(a), ExprT::Inner(b)) => a.structural_eq(b),
+            (ExprT::Bracket(a_bt, a), ExprT::Bracket(b_bt, b)) => a_bt == b_bt && all(a, b),
+            // Everything else carries no sub spans - plain
+            //     equality of the variant data is exact.
+            (a, b) => a == b,
+        }
+    }
+
+    pub(crate) fn shift_positions(&mut self, from: usize, delta: isize) {
+        self.span.shift_from(from, delta);
+        match &mut self.expr {
+            ExprT::Inner(inner) => inner.shift_positions(from, delta),
+            ExprT::Bracket(_, parts) => {
+                for sent in parts {
+                    sent.shift_positions(from, delta)
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -1,13 +1,45 @@
-use crate::common::error::error_struct;
+use crate::common::error::{error_struct, warning_struct};
 
 error_struct!(UnsupportedSymbol, "this symbol isn't supported",);
 error_struct!(UnexpectedEOS, "EOS wasn't expected here",);
 error_struct!(UnexpectedSymbol, "symbol `{}` wasn't expected here", symbol: char);
 error_struct!(ParseInt, "cannot parse `{}` as i64", int: String);
+error_struct!(ParseFloat, "cannot parse `{}` as f64", float: String);
+error_struct!(InvalidUnicodeEscape, "`{}` isn't a valid unicode scalar", code: String);
+error_struct!(EmptyCharLiteral, "char literal shouldn't be empty",);
 error_struct!(ClosedBracket, "cannot find open pair for this bracket",);
+error_struct!(
+    MismatchedBracket,
+    "expected `{}` to close `{}` opened at {:?}",
+    expected: char,
+    open: char,
+    opened: crate::common::location::Span
+);
 error_struct!(ClosingBracketNotFound, "cannot find closing bracket",);
 error_struct!(UnexpectedToken, "`inner` cannot be followed by this",);
-error_struct!(EmptyPartInBrackets, "parts in brackets shouldn't be empty",);
+error_struct!(EmptyPartBeforeComma, "empty element before comma",);
+error_struct!(NestingTooDeep, "nesting deeper than {} levels", limit: usize);
 error_struct!(UnexpectedEndOfLine, "New line wasn't expected here",);
 error_struct!(WrongLineOffset, "unexpected offset {}", offset: usize);
+error_struct!(
+    DedentMismatch,
+    "dedent to offset {} does not match any enclosing block",
+    offset: usize
+);
 error_struct!(NewLineOnFileEnd, "unexpected new line on the end of file",);
+error_struct!(MixedIndentation, "indentation mixes tabs and spaces",);
+error_struct!(UnterminatedComment, "block comment isn't terminated",);
+error_struct!(ReadFailed, "cannot read source: {}", reason: String);
+
+warning_struct!(TrailingComma, "redundant trailing comma",);
+warning_struct!(
+    TabIndentation,
+    "tab indentation, interpreted as {} spaces per tab",
+    spaces: usize
+);
+warning_struct!(
+    LineTooLong,
+    "line is {} chars long, the limit is {}",
+    length: usize,
+    limit: usize
+);
@@ -0,0 +1,179 @@
+use super::lexer::{Lexer, Token};
+use super::symbol::SymbolType;
+use super::ParseConfig;
+
+use crate::common::location::Span;
+
+use std::iter::Peekable;
+
+/// Coarse token category for editor highlighting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenClass {
+    Keyword,
+    Identifier,
+    Number,
+    String,
+    Comment,
+    Operator,
+    Bracket,
+}
+
+type Tokens<'a> = Peekable<Lexer<'a>>;
+
+/// Classification spans for editor highlighting, one pass over the
+///     lexer without any parsing. Never fails: unlexable input gets
+///     a best-effort class from its first char, so an unterminated
+///     string still paints to the end of the line.
+pub fn highlight(line: &str) -> Vec<(Span, TokenClass)> {
+    highlight_with_config(line, &Default::default())
+}
+
+/// Same, honoring `ParseConfig::keywords` and `comment_marker`.
+pub fn highlight_with_config(line: &str, config: &ParseConfig) -> Vec<(Span, TokenClass)> {
+    let mut result = Vec::new();
+    let mut tokens = Lexer::new(line).peekable();
+    while let Some(token) = tokens.next() {
+        let (token, span) = match token {
+            Ok(ok) => ok,
+            Err(e) => {
+                if let Some(class) = error_class(line, e.span()) {
+                    result.push((e.span(), class))
+                }
+                continue;
+            }
+        };
+        match token {
+            Token::Word(w) if config.keywords.contains(&w.to_string().as_str()) => {
+                result.push((span, TokenClass::Keyword))
+            }
+            Token::Word(_) => result.push((span, TokenClass::Identifier)),
+            Token::LitInt(..) | Token::LitFloat(..) => result.push((span, TokenClass::Number)),
+            Token::LitStr(_) | Token::LitChar(_) => result.push((span, TokenClass::String)),
+            Token::Comment => result.push((span, TokenClass::Comment)),
+            Token::Bracket(..) => result.push((span, TokenClass::Bracket)),
+            // `. `/`..` comment out the rest of the line. Whether
+            //     the dot sits where an expression may start isn't
+            //     tracked here - highlighting over-approximates.
+            Token::Dot => match tokens.peek() {
+                Some(Ok((Token::Whitespace(_) | Token::Dot, s2))) if span.end() == s2.begin() => {
+                    result.push((rest_of_line(&mut tokens, span), TokenClass::Comment))
+                }
+                _ => {}
+            },
+            Token::Special(s) => special(&mut tokens, s.to_string(), span, config, &mut result),
+            Token::Comma | Token::Whitespace(_) | Token::Tabulation(_) | Token::NewLine => {}
+        }
+    }
+    result
+}
+
+// An operator, unless it begins the configured `comment_marker` -
+//     then adjacent specials are matched against the marker and a
+//     full match comments out the rest of the line.
+fn special(
+    tokens: &mut Tokens,
+    text: String,
+    span: Span,
+    config: &ParseConfig,
+    result: &mut Vec<(Span, TokenClass)>,
+) {
+    let marker = match config.comment_marker {
+        Some(marker) if marker.starts_with(&text) => marker,
+        _ => return result.push((span, TokenClass::Operator)),
+    };
+    let mut matched = text;
+    let mut end = span;
+    let mut parts = vec![span];
+    while matched != marker {
+        match tokens.peek() {
+            Some(Ok((Token::Special(next), s2)))
+                if end.end() == s2.begin()
+                    && marker[matched.len()..].starts_with(&next.to_string()) =>
+            {
+                matched.push_str(&next.to_string());
+                end = *s2;
+                parts.push(*s2);
+                tokens.next();
+            }
+            _ => break,
+        }
+    }
+    match matched == marker {
+        true => result.push((rest_of_line(tokens, span + end), TokenClass::Comment)),
+        false => result.extend(parts.into_iter().map(|part| (part, TokenClass::Operator))),
+    }
+}
+
+fn rest_of_line(tokens: &mut Tokens, from: Span) -> Span {
+    let mut to = from;
+    while let Some(token) = tokens.next() {
+        match token {
+            Ok((Token::NewLine, _)) => break,
+            Ok((_, span)) => to = span,
+            Err(e) => to = e.span(),
+        }
+    }
+    from + to
+}
+
+// Maps an unlexable span to a class by its first char: a broken
+//     string is still a string, a broken number still a number.
+fn error_class(line: &str, span: Span) -> Option<TokenClass> {
+    let first = line.chars().nth(span.begin().as_usize())?;
+    Some(match SymbolType::from(first) {
+        SymbolType::Quote | SymbolType::SingleQuote => TokenClass::String,
+        SymbolType::Digit(_) => TokenClass::Number,
+        SymbolType::Letter(_) => TokenClass::Identifier,
+        // The only unlexable `/` is an unterminated block comment.
+        SymbolType::Special('/') => TokenClass::Comment,
+        SymbolType::Special(_) => TokenClass::Operator,
+        SymbolType::Bracket(..) => TokenClass::Bracket,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn classes(line: &str) -> Vec<TokenClass> {
+        highlight(line).into_iter().map(|(_, class)| class).collect()
+    }
+
+    #[test]
+    fn basic_classes() {
+        use TokenClass::*;
+        assert_eq!(
+            classes("f (x + 1, \"s\")\n"),
+            [Identifier, Bracket, Identifier, Operator, Number, String, Bracket]
+        );
+        assert_eq!(classes(". note\n"), [Comment]);
+        assert_eq!(classes("x .. doc\n"), [Identifier, Comment]);
+    }
+
+    #[test]
+    fn never_errors() {
+        // Unterminated string: one string span to the end.
+        let spans = highlight("f \"abc");
+        assert_eq!(spans[1].1, TokenClass::String);
+        assert_eq!(spans[1].0.end().as_usize(), "f \"abc".chars().count());
+        // Garbage still yields the lexable part.
+        assert!(!highlight("x \u{1} y\n").is_empty());
+    }
+
+    #[test]
+    fn config_classes() {
+        let config = ParseConfig {
+            keywords: &["let"],
+            comment_marker: Some("//"),
+            ..Default::default()
+        };
+        let spans = highlight_with_config("let x // c\n", &config);
+        let classes: Vec<_> = spans.iter().map(|(_, class)| *class).collect();
+        use TokenClass::*;
+        assert_eq!(classes, [Keyword, Identifier, Comment]);
+        // The comment span runs from the marker to the end of line.
+        assert_eq!(spans[2].0.begin().as_usize(), 6);
+        assert_eq!(spans[2].0.end().as_usize(), 10);
+    }
+}
@@ -2,8 +2,11 @@ use crate::common::error::{raise_error, Result};
 use crate::common::location::{Position, Span};
 use crate::common::symbol::Symbol;
 
-use super::errors::{ParseInt, UnexpectedEOS, UnexpectedSymbol, UnsupportedSymbol};
-use super::symbol::{BracketType, SymbolType};
+use super::errors::{
+    EmptyCharLiteral, InvalidUnicodeEscape, ParseFloat, ParseInt, UnexpectedEOS, UnexpectedSymbol,
+    UnsupportedSymbol, UnterminatedComment,
+};
+use super::symbol::{operator_prefix, BracketType, Radix, SymbolType};
 
 use std::iter::Peekable;
 use std::str::Chars;
@@ -16,10 +19,14 @@ pub enum Token {
     NewLine,
     Bracket(BracketType, bool),
     Whitespace(usize),
+    Tabulation(usize),
+    Comment,
     Special(Symbol),
     Word(Symbol),
-    LitInt(i64),
+    LitInt(i64, Radix, Option<Symbol>),
+    LitFloat(f64, Option<Symbol>),
     LitStr(String),
+    LitChar(char),
 }
 
 pub struct Lexer<'a> {
@@ -39,10 +46,17 @@ impl<'a> Lexer<'a> {
             SymbolType::NewLine => Token::NewLine,
             SymbolType::Bracket(bt, open) => Token::Bracket(bt, open),
             SymbolType::Quote => string(&mut self.stream, begin)?,
+            SymbolType::SingleQuote => char_lit(&mut self.stream, begin)?,
+            SymbolType::Letter('r') => maybe_raw_string(&mut self.stream, begin)?,
             SymbolType::Letter(c) => word(&mut self.stream, begin, c)?,
             SymbolType::Digit(c) => number(&mut self.stream, begin, c)?,
+            SymbolType::Special('/') if self.stream.chars.peek() == Some(&'*') => {
+                block_comment(&mut self.stream, begin)?
+            }
             SymbolType::Special(c) => special(&mut self.stream, begin, c)?,
             SymbolType::Whitespace(w) => whitespace(&mut self.stream, begin, w)?,
+            SymbolType::Tab => tabs(&mut self.stream, begin)?,
+            SymbolType::Backslash => continuation(&mut self.stream, begin)?,
             _ => raise_error!(UnsupportedSymbol, self.stream.span(begin),),
         };
         Ok((result, self.stream.span(begin)))
@@ -62,17 +76,19 @@ impl<'a> Iterator for Lexer<'a> {
 }
 
 fn string(stream: &mut Stream, begin: Position) -> Result<Token> {
+    if stream.chars.peek() == Some(&'"') {
+        stream.next().unwrap();
+        if stream.chars.peek() == Some(&'"') {
+            stream.next().unwrap();
+            return triple_string(stream, begin);
+        }
+        return Ok(Token::LitStr(String::new()));
+    }
     let mut result = String::new();
     loop {
+        let char_begin = stream.pos;
         match stream.next() {
-            Some('\\') => result.push(match stream.next() {
-                Some('\\') => '\\',
-                Some('n') => '\n',
-                Some('t') => '\t',
-                Some('"') => '"',
-                Some(c) => raise_error!(UnexpectedSymbol, stream.span(begin), c),
-                None => raise_error!(UnexpectedEOS, stream.span(begin),),
-            }),
+            Some('\\') => result.push(escape(stream, char_begin)?),
             Some('"') => return Ok(Token::LitStr(result)),
             Some(c) => result.push(c),
             None => raise_error!(UnexpectedEOS, stream.span(begin),),
@@ -80,6 +96,132 @@ fn string(stream: &mut Stream, begin: Position) -> Result<Token> {
     }
 }
 
+// Opening `"""` is already consumed.
+// Lexing happens before line splitting and `Position` is an offset in
+//     the whole file, so the literal may span several physical lines.
+fn triple_string(stream: &mut Stream, begin: Position) -> Result<Token> {
+    let mut result = String::new();
+    loop {
+        let char_begin = stream.pos;
+        match stream.next() {
+            Some('\\') => result.push(escape(stream, char_begin)?),
+            Some('"') => {
+                let mut quotes = 1;
+                while quotes < 3 && stream.chars.peek() == Some(&'"') {
+                    stream.next().unwrap();
+                    quotes += 1;
+                }
+                if quotes == 3 {
+                    return Ok(Token::LitStr(result));
+                }
+                result.extend(std::iter::repeat('"').take(quotes))
+            }
+            Some(c) => result.push(c),
+            None => raise_error!(UnexpectedEOS, stream.span(begin),),
+        }
+    }
+}
+
+// "r" followed by a quote (or hashes and a quote) opens a raw string,
+//     any other continuation is an ordinary word.
+fn maybe_raw_string(stream: &mut Stream, begin: Position) -> Result<Token> {
+    match stream.chars.peek() {
+        Some('"') | Some('#') => raw_string(stream, begin),
+        _ => word(stream, begin, 'r'),
+    }
+}
+
+// Leading "r" is already consumed.
+// No escape processing: bytes are copied verbatim until `"` followed
+//     by the same number of "#"s as the opening delimiter had.
+fn raw_string(stream: &mut Stream, begin: Position) -> Result<Token> {
+    let mut hashes = 0;
+    while let Some('#') = stream.chars.peek() {
+        stream.next().unwrap();
+        hashes += 1;
+    }
+    match stream.next() {
+        Some('"') => {}
+        Some(c) => raise_error!(UnexpectedSymbol, stream.span(begin), c),
+        None => raise_error!(UnexpectedEOS, stream.span(begin),),
+    }
+    let mut result = String::new();
+    loop {
+        match stream.next() {
+            Some('"') => {
+                let mut seen = 0;
+                while seen < hashes && stream.chars.peek() == Some(&'#') {
+                    stream.next().unwrap();
+                    seen += 1;
+                }
+                if seen == hashes {
+                    return Ok(Token::LitStr(result));
+                }
+                // Not a matching close - the quote and hashes are content.
+                result.push('"');
+                result.extend(std::iter::repeat('#').take(seen))
+            }
+            Some(c) => result.push(c),
+            None => raise_error!(UnexpectedEOS, stream.span(begin),),
+        }
+    }
+}
+
+// Opening "'" is already consumed.
+fn char_lit(stream: &mut Stream, begin: Position) -> Result<Token> {
+    let char_begin = stream.pos;
+    let result = match stream.next() {
+        Some('\'') => raise_error!(EmptyCharLiteral, stream.span(begin),),
+        Some('\\') => escape(stream, char_begin)?,
+        Some(c) => c,
+        None => raise_error!(UnexpectedEOS, stream.span(begin),),
+    };
+    match stream.next() {
+        Some('\'') => Ok(Token::LitChar(result)),
+        Some(c) => raise_error!(UnexpectedSymbol, stream.span(begin), c),
+        None => raise_error!(UnexpectedEOS, stream.span(begin),),
+    }
+}
+
+// `begin` points at the already consumed backslash,
+//     so errors span the whole escape sequence.
+fn escape(stream: &mut Stream, begin: Position) -> Result<char> {
+    Ok(match stream.next() {
+        Some('\\') => '\\',
+        Some('n') => '\n',
+        Some('t') => '\t',
+        Some('r') => '\r',
+        Some('0') => '\0',
+        Some('"') => '"',
+        Some('\'') => '\'',
+        Some('u') => unicode_escape(stream, begin)?,
+        Some(c) => raise_error!(UnexpectedSymbol, stream.span(begin), c),
+        None => raise_error!(UnexpectedEOS, stream.span(begin),),
+    })
+}
+
+// "\u" is already consumed; reads "{XXXX}" and validates the codepoint.
+fn unicode_escape(stream: &mut Stream, begin: Position) -> Result<char> {
+    match stream.next() {
+        Some('{') => {}
+        Some(c) => raise_error!(UnexpectedSymbol, stream.span(begin), c),
+        None => raise_error!(UnexpectedEOS, stream.span(begin),),
+    }
+    let mut hex = String::new();
+    loop {
+        match stream.next() {
+            Some('}') => break,
+            Some(c) if c.is_ascii_hexdigit() => hex.push(c),
+            Some(c) => raise_error!(UnexpectedSymbol, stream.span(begin), c),
+            None => raise_error!(UnexpectedEOS, stream.span(begin),),
+        }
+    }
+    match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+        Some(c) => Ok(c),
+        None => raise_error!(InvalidUnicodeEscape, stream.span(begin), hex),
+    }
+}
+
 fn whitespace(stream: &mut Stream, begin: Position, first: usize) -> Result<Token> {
     let mut result = first;
     loop {
@@ -94,44 +236,254 @@ fn whitespace(stream: &mut Stream, begin: Position, first: usize) -> Result<Toke
     }
 }
 
+// `\` closing a physical line joins it with the next one: the
+//     backslash, any trailing blanks and the line break all melt
+//     into one whitespace token, so no `NewLine` is emitted, every
+//     span still points into the source and the continuation
+//     indentation isn't an offset. Joining happens on tokens, not
+//     text: a backslash inside a string is consumed as an escape
+//     or verbatim content and never reaches here. Anywhere else
+//     on a line the char stays unsupported.
+fn continuation(stream: &mut Stream, begin: Position) -> Result<Token> {
+    let mut width = 1;
+    loop {
+        match stream.chars.peek() {
+            Some(' ') | Some('\t') => {
+                width += 1;
+                stream.next().unwrap();
+            }
+            Some('\n') => {
+                stream.next().unwrap();
+                return Ok(Token::Whitespace(width + 1));
+            }
+            _ => raise_error!(UnsupportedSymbol, stream.span(begin),),
+        }
+    }
+}
+
+fn tabs(stream: &mut Stream, begin: Position) -> Result<Token> {
+    let mut result = 1;
+    loop {
+        match SymbolType::from(stream.chars.peek().map(|&c| c)) {
+            SymbolType::Tab => {
+                result += 1;
+                stream.next().unwrap();
+            }
+            SymbolType::Other(_) => raise_error!(UnsupportedSymbol, stream.span(begin),),
+            _ => return Ok(Token::Tabulation(result)),
+        }
+    }
+}
+
 fn word(stream: &mut Stream, begin: Position, start: char) -> Result<Token> {
     let mut result = String::from(start);
     loop {
         match SymbolType::from(stream.chars.peek().map(|&c| c)) {
             SymbolType::Letter(_) | SymbolType::Digit(_) => result.push(stream.next().unwrap()),
+            // Non-ascii digits may continue a word, but not start one.
+            SymbolType::Other(c) if c.is_alphanumeric() => result.push(stream.next().unwrap()),
             SymbolType::Other(_) => raise_error!(UnsupportedSymbol, stream.span(begin),),
             _ => return Ok(Token::Word(Symbol::from(result))),
         }
     }
 }
 
+// Greedily matches the longest operator from `symbol::OPERATORS`;
+//     sequences outside the table stay single-char tokens.
 fn special(stream: &mut Stream, begin: Position, start: char) -> Result<Token> {
     let mut result = String::from(start);
     loop {
         match SymbolType::from(stream.chars.peek().map(|&c| c)) {
-            SymbolType::Special(_) => result.push(stream.next().unwrap()),
+            SymbolType::Special(c) if operator_prefix(&result, c) => {
+                result.push(stream.next().unwrap())
+            }
             SymbolType::Other(_) => raise_error!(UnsupportedSymbol, stream.span(begin),),
             _ => return Ok(Token::Special(Symbol::from(result))),
         }
     }
 }
 
-// To be done: parse other numbers. For now, `dot` is unexpected.
+// "/" is already consumed, "*" is the next char.
+// Block comments nest and shouldn't cross a line boundary.
+fn block_comment(stream: &mut Stream, begin: Position) -> Result<Token> {
+    stream.next().unwrap();
+    let mut depth = 1;
+    while depth > 0 {
+        match stream.next() {
+            Some('/') if stream.chars.peek() == Some(&'*') => {
+                stream.next().unwrap();
+                depth += 1
+            }
+            Some('*') if stream.chars.peek() == Some(&'/') => {
+                stream.next().unwrap();
+                depth -= 1
+            }
+            Some('\n') | None => {
+                raise_error!(UnterminatedComment, Span::new(begin, begin.advanced(2)),)
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(Token::Comment)
+}
+
 fn number(stream: &mut Stream, begin: Position, start: char) -> Result<Token> {
+    if start == '0' {
+        let radix = match stream.chars.peek() {
+            Some('b') => Some(Radix::Binary),
+            Some('o') => Some(Radix::Octal),
+            Some('x') => Some(Radix::Hexadecimal),
+            _ => None,
+        };
+        if let Some(radix) = radix {
+            stream.next().unwrap();
+            return radix_int(stream, begin, radix);
+        }
+    }
     let mut result = String::from(start);
     loop {
         match SymbolType::from(stream.chars.peek().map(|&c| c)) {
-            SymbolType::Letter(_) | SymbolType::Digit(_) => result.push(stream.next().unwrap()),
+            SymbolType::Letter('_') => digit_separator(stream, Radix::Decimal)?,
+            SymbolType::Letter('e') | SymbolType::Letter('E') => {
+                return exponent(stream, begin, result)
+            }
+            SymbolType::Digit(_) => result.push(stream.next().unwrap()),
+            // A letter glued to the digits starts a typed suffix: `5u8`.
+            SymbolType::Letter(_) => match result.parse::<i64>() {
+                Ok(r) => return Ok(Token::LitInt(r, Radix::Decimal, Some(suffix(stream)))),
+                Err(_) => raise_error!(ParseInt, stream.span(begin), result),
+            },
             SymbolType::Other(_) => raise_error!(UnsupportedSymbol, stream.span(begin),),
-            SymbolType::Dot => raise_error!(UnexpectedSymbol, stream.span(begin), '.'),
+            SymbolType::Dot => return float(stream, begin, result),
             _ => match result.parse::<i64>() {
-                Ok(r) => return Ok(Token::LitInt(r)),
+                Ok(r) => return Ok(Token::LitInt(r, Radix::Decimal, None)),
                 Err(_) => raise_error!(ParseInt, stream.span(begin), result),
             },
         }
     }
 }
 
+// Mantissa is already consumed, "e"/"E" is the next char.
+// The sign is handled here: "+"/"-" are `Special` and would
+//     never be consumed by the digit loops.
+fn exponent(stream: &mut Stream, begin: Position, mut result: String) -> Result<Token> {
+    result.push(stream.next().unwrap());
+    if let Some('+') | Some('-') = stream.chars.peek() {
+        result.push(stream.next().unwrap())
+    }
+    let mut digits = 0;
+    loop {
+        match SymbolType::from(stream.chars.peek().map(|&c| c)) {
+            SymbolType::Digit(_) => {
+                digits += 1;
+                result.push(stream.next().unwrap())
+            }
+            SymbolType::Letter(_) if digits > 0 => {
+                return match result.parse::<f64>() {
+                    Ok(r) => Ok(Token::LitFloat(r, Some(suffix(stream)))),
+                    Err(_) => raise_error!(ParseFloat, stream.span(begin), result),
+                }
+            }
+            SymbolType::Letter(_) | SymbolType::Dot => {
+                raise_error!(ParseFloat, stream.span(begin), result)
+            }
+            SymbolType::Other(_) => raise_error!(UnsupportedSymbol, stream.span(begin),),
+            _ => break,
+        }
+    }
+    if digits == 0 {
+        raise_error!(ParseFloat, stream.span(begin), result)
+    }
+    match result.parse::<f64>() {
+        Ok(r) => Ok(Token::LitFloat(r, None)),
+        Err(_) => raise_error!(ParseFloat, stream.span(begin), result),
+    }
+}
+
+// Consumes one "_" separator, which must be followed by a digit:
+// trailing (`5_`) and doubled (`5__0`) separators are rejected.
+fn digit_separator(stream: &mut Stream, radix: Radix) -> Result<()> {
+    let separator = stream.pos;
+    stream.next().unwrap();
+    match SymbolType::from(stream.chars.peek().map(|&c| c)) {
+        SymbolType::Letter(c) | SymbolType::Digit(c) if c.is_digit(radix.base()) => Ok(()),
+        _ => raise_error!(UnexpectedSymbol, stream.span(separator), '_'),
+    }
+}
+
+// "0b"/"0o"/"0x" prefix is already consumed.
+fn radix_int(stream: &mut Stream, begin: Position, radix: Radix) -> Result<Token> {
+    let mut result = String::new();
+    loop {
+        match SymbolType::from(stream.chars.peek().map(|&c| c)) {
+            SymbolType::Letter('_') => digit_separator(stream, radix)?,
+            // A letter outside the radix after at least one digit is a suffix:
+            //     `0xFFu8`. Out-of-radix digits (`0o9`) stay errors.
+            SymbolType::Letter(c) if !c.is_digit(radix.base()) && !result.is_empty() => {
+                return match i64::from_str_radix(&result, radix.base()) {
+                    Ok(r) => Ok(Token::LitInt(r, radix, Some(suffix(stream)))),
+                    Err(_) => raise_error!(ParseInt, stream.span(begin), result),
+                }
+            }
+            SymbolType::Letter(c) | SymbolType::Digit(c) => {
+                if !c.is_digit(radix.base()) {
+                    let offending = stream.pos;
+                    stream.next().unwrap();
+                    raise_error!(UnexpectedSymbol, stream.span(offending), c)
+                }
+                result.push(stream.next().unwrap())
+            }
+            SymbolType::Other(_) => raise_error!(UnsupportedSymbol, stream.span(begin),),
+            SymbolType::Dot => raise_error!(UnexpectedSymbol, stream.span(begin), '.'),
+            _ => match i64::from_str_radix(&result, radix.base()) {
+                Ok(r) => return Ok(Token::LitInt(r, radix, None)),
+                Err(_) => raise_error!(ParseInt, stream.span(begin), result),
+            },
+        }
+    }
+}
+
+// Integer part is already consumed, `stream` stays on the dot.
+// Dot not followed by a digit isn't a fractional part (`3.` is incomplete).
+fn float(stream: &mut Stream, begin: Position, mut result: String) -> Result<Token> {
+    stream.next().unwrap();
+    match SymbolType::from(stream.chars.peek().map(|&c| c)) {
+        SymbolType::Digit(_) => result.push('.'),
+        _ => raise_error!(UnexpectedSymbol, stream.span(begin), '.'),
+    }
+    loop {
+        match SymbolType::from(stream.chars.peek().map(|&c| c)) {
+            SymbolType::Letter('e') | SymbolType::Letter('E') => {
+                return exponent(stream, begin, result)
+            }
+            SymbolType::Letter(_) => match result.parse::<f64>() {
+                Ok(r) => return Ok(Token::LitFloat(r, Some(suffix(stream)))),
+                Err(_) => raise_error!(ParseFloat, stream.span(begin), result),
+            },
+            SymbolType::Digit(_) => result.push(stream.next().unwrap()),
+            SymbolType::Other(_) => raise_error!(UnsupportedSymbol, stream.span(begin),),
+            // Second dot (`3.4.5`) cannot continue a number.
+            SymbolType::Dot => raise_error!(UnexpectedSymbol, stream.span(begin), '.'),
+            _ => match result.parse::<f64>() {
+                Ok(r) => return Ok(Token::LitFloat(r, None)),
+                Err(_) => raise_error!(ParseFloat, stream.span(begin), result),
+            },
+        }
+    }
+}
+
+// Collects the identifier glued to a numeric literal (`u8` in `5u8`).
+// The caller already peeked a letter, so the result is never empty.
+fn suffix(stream: &mut Stream) -> Symbol {
+    let mut result = String::new();
+    loop {
+        match SymbolType::from(stream.chars.peek().map(|&c| c)) {
+            SymbolType::Letter(_) | SymbolType::Digit(_) => result.push(stream.next().unwrap()),
+            _ => return Symbol::from(result),
+        }
+    }
+}
+
 #[derive(derive_new::new)]
 struct Stream<'a> {
     chars: Peekable<Chars<'a>>,
@@ -155,3 +507,95 @@ impl<'a> Iterator for Stream<'a> {
         next
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lex_one(code: &str) -> Result<(Token, Span)> {
+        Lexer::new(code).next().unwrap()
+    }
+
+    #[test]
+    fn digit_separators() {
+        assert!(matches!(
+            lex_one("1_000_000"),
+            Ok((Token::LitInt(1000000, Radix::Decimal, None), _))
+        ));
+        assert!(matches!(
+            lex_one("0xF_F"),
+            Ok((Token::LitInt(255, Radix::Hexadecimal, None), _))
+        ));
+        // Leading underscore is a word, not a number.
+        assert!(matches!(lex_one("_5"), Ok((Token::Word(_), _))));
+        assert!(lex_one("5_").is_err());
+        assert!(lex_one("5__0").is_err());
+    }
+
+    #[test]
+    fn scientific_notation() {
+        assert!(matches!(lex_one("1e3"), Ok((Token::LitFloat(f, None), _)) if f == 1000.0));
+        assert!(matches!(lex_one("2.5e-3"), Ok((Token::LitFloat(f, None), _)) if f == 0.0025));
+        assert!(matches!(lex_one("6.02E23"), Ok((Token::LitFloat(_, None), _))));
+        assert!(lex_one("1e").is_err());
+        assert!(lex_one("1e+").is_err());
+    }
+
+    // A control char inside a whitespace run is an error, never
+    //     a panic, and a newline just ends the run.
+    #[test]
+    fn control_chars_in_whitespace() {
+        assert!(lex_one("  \u{1}x").is_err());
+        assert!(matches!(lex_one("  \nx"), Ok((Token::Whitespace(2), _))));
+        assert!(matches!(lex_one("\t\u{b}"), Err(_)));
+    }
+
+    #[test]
+    fn multi_char_operators() {
+        let text = |code: &str| match lex_one(code) {
+            Ok((Token::Special(s), _)) => s.to_string(),
+            other => panic!("expected a special token, got {:?}", other),
+        };
+        assert_eq!(text("=="), "==");
+        assert_eq!(text("=> x"), "=>");
+        assert_eq!(text("<<="), "<<=");
+        // Unknown combinations stay single-char.
+        assert_eq!(text("=!"), "=");
+    }
+
+    #[test]
+    fn numeric_suffixes() {
+        let suffixed = |code: &str| match lex_one(code) {
+            Ok((Token::LitInt(i, _, Some(s)), _)) => (i, s.to_string()),
+            other => panic!("expected a suffixed int, got {:?}", other),
+        };
+        assert_eq!(suffixed("5u8"), (5, "u8".to_string()));
+        assert_eq!(suffixed("5_000u32"), (5000, "u32".to_string()));
+        assert_eq!(suffixed("0xFFu8"), (255, "u8".to_string()));
+        assert!(matches!(
+            lex_one("3.0f32"),
+            Ok((Token::LitFloat(f, Some(_)), _)) if f == 3.0
+        ));
+        assert!(matches!(lex_one("1e3f64"), Ok((Token::LitFloat(_, Some(_)), _))));
+        // Separated by whitespace the suffix is an ordinary word.
+        let mut lexer = Lexer::new("5 u8");
+        assert!(matches!(
+            lexer.next(),
+            Some(Ok((Token::LitInt(5, _, None), _)))
+        ));
+        lexer.next();
+        assert!(matches!(lexer.next(), Some(Ok((Token::Word(_), _)))));
+        // The suffix is part of the literal's span.
+        let (_, span) = lex_one("5u8").unwrap();
+        assert_eq!(format!("{:?}", span), "Span(0, 3)");
+    }
+
+    #[test]
+    fn unicode_identifiers() {
+        assert!(matches!(lex_one("café"), Ok((Token::Word(_), _))));
+        assert!(matches!(lex_one("λ"), Ok((Token::Word(_), _))));
+        // Spans count chars, not bytes.
+        let (_, span) = lex_one("café").unwrap();
+        assert_eq!(format!("{:?}", span), "Span(0, 4)");
+    }
+}
@@ -1,25 +1,68 @@
 use std::iter::Peekable;
 use std::vec;
 
-use super::ast::{Expr, Line, Sent};
+use super::ast::{Expr, ExprT, Line, Sent};
 use super::errors::{
-    ClosedBracket, ClosingBracketNotFound, EmptyPartInBrackets, NewLineOnFileEnd,
+    ClosedBracket, ClosingBracketNotFound, EmptyPartBeforeComma, LineTooLong, MismatchedBracket,
+    MixedIndentation, NestingTooDeep, NewLineOnFileEnd, TabIndentation, TrailingComma,
     UnexpectedEndOfLine, UnexpectedSymbol, UnexpectedToken, WrongLineOffset,
 };
 use super::lexer::{Lexer, Token};
-use super::symbol::{offset, BracketType};
+use super::symbol::{offset, BracketType, TAB_TO_SPACES};
+use super::ParseConfig;
 
-use crate::common::error::{raise_error, Error};
-use crate::common::location::Span;
+use crate::common::error::{raise_error, Error, ErrorKind, Severity};
+use crate::common::location::{Position, Span};
 use crate::common::symbol::Symbol;
 
 // To be done: fix risen (after fixing using slices) code complexity.
 
-pub fn parse(line: &str) -> Result<Vec<(usize, Line)>, Vec<Error>> {
+// On success the second value holds accumulated warnings:
+//     diagnostics with `Severity::Warning` never fail the parse.
+pub fn parse(
+    line: &str,
+    config: &ParseConfig,
+) -> Result<(Vec<(usize, Line)>, Vec<Error>), Vec<Error>> {
     // To be done: remove unnecessary allocations.
+    let mut errors = Vec::new();
+    // Measured on the raw text before the shebang rewrite, so
+    //     every physical line keeps its real length. The span
+    //     covers only the overflowing tail.
+    if let Some(limit) = config.max_line_length {
+        let mut begin = 0;
+        for text in line.split('\n') {
+            let length = text.chars().count();
+            if length > limit {
+                let span = Span::new(
+                    Position::new(begin + limit).unwrap(),
+                    Position::new(begin + length).unwrap(),
+                );
+                errors.push(Box::new(LineTooLong::new(span, length, limit)) as Error)
+            }
+            begin += length + 1
+        }
+    }
+    // `#!` opening the very first line is a shebang. It's blanked
+    //     out rather than cut, so every later span keeps its
+    //     offset, and the all-space line is then dropped as blank.
+    let blanked;
+    let line = match line.starts_with("#!") {
+        true => {
+            let end = line.find('\n').unwrap_or(line.len());
+            blanked = line[..end]
+                .chars()
+                .map(|_| ' ')
+                .chain(line[end..].chars())
+                .collect::<String>();
+            blanked.as_str()
+        }
+        false => line,
+    };
+    // `\` continuations are joined by the lexer itself (no
+    //     `NewLine` token is emitted for them), so a backslash
+    //     inside a string literal is never mistaken for one.
     let mut lines = Vec::new();
     let mut l_cur = Vec::new();
-    let mut errors = Vec::new();
     for token in Lexer::new(line) {
         match token {
             Ok((Token::NewLine, _)) => {
@@ -34,38 +77,162 @@ pub fn parse(line: &str) -> Result<Vec<(usize, Line)>, Vec<Error>> {
         return Err(vec![Box::new(NewLineOnFileEnd::new(Default::default()))]);
     }
 
+    // A bracket left open at the end of a line continues on the
+    //     following lines up to the matching close, so bracketed
+    //     parts may span multiple lines. A bracket still open at
+    //     EOF is reported at its opening position.
+    let mut merged: Vec<Vec<(Token, Span)>> = Vec::new();
+    let mut depth = 0usize;
+    for line in lines {
+        let continuation = depth > 0 && !merged.is_empty();
+        for (token, _) in &line {
+            match token {
+                Token::Bracket(_, true) => depth += 1,
+                Token::Bracket(_, false) => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+        if continuation {
+            // Continuation indentation isn't part of the content.
+            let indentation = matches!(
+                line.first(),
+                Some((Token::Whitespace(_) | Token::Tabulation(_), _))
+            ) as usize;
+            merged.last_mut().unwrap().extend(line.into_iter().skip(indentation))
+        } else {
+            merged.push(line)
+        }
+    }
+
     let mut result = Vec::new();
-    for mut line in lines.into_iter() {
+    for mut line in merged.into_iter() {
+        // Leading run mixing tabs and spaces is ambiguous indentation.
+        if !config.free_form {
+            if let (Some((Token::Whitespace(_), s)), Some((Token::Tabulation(_), _)))
+            | (Some((Token::Tabulation(_), s)), Some((Token::Whitespace(_), _))) =
+                (line.first(), line.get(1))
+            {
+                errors.push(Box::new(MixedIndentation::new(*s)));
+                continue;
+            }
+        }
         let (of, iter) = match line.first().map(|i| i.clone()) {
-            Some((Token::Whitespace(w), s)) if line.len() > 1 => match offset(w) {
-                Some(of) => (of, line.drain(1..).collect()),
-                None => {
-                    errors.push(Box::new(WrongLineOffset::new(s, w)));
-                    continue;
+            // Free-form mode: leading whitespace is insignificant
+            //     and every line sits at offset zero.
+            Some((Token::Whitespace(_) | Token::Tabulation(_), _))
+                if config.free_form && line.len() > 1 =>
+            {
+                (0, line.drain(1..).collect())
+            }
+            Some((Token::Whitespace(w), s)) if line.len() > 1 => {
+                match offset(w, config.indent_width as usize) {
+                    Some(of) => (of, line.drain(1..).collect()),
+                    None => {
+                        errors.push(Box::new(WrongLineOffset::new(s, w)));
+                        continue;
+                    }
+                }
+            }
+            Some((Token::Tabulation(t), s)) if line.len() > 1 => {
+                if config.tab_indent {
+                    (t, line.drain(1..).collect())
+                } else {
+                    errors.push(Box::new(TabIndentation::new(s, TAB_TO_SPACES)));
+                    match offset(t * TAB_TO_SPACES, config.indent_width as usize) {
+                        Some(of) => (of, line.drain(1..).collect()),
+                        None => {
+                            errors.push(Box::new(WrongLineOffset::new(s, t)));
+                            continue;
+                        }
+                    }
                 }
-            },
+            }
             Some((_, _)) if line.len() > 0 => (0, line),
             _ => continue,
         };
-        match parse_line(&mut iter.into_iter().peekable()) {
-            Ok(Some(line)) => result.push((of, line)),
-            Ok(None) => {}
-            Err(e) => errors.push(e),
+        let parts = match config.semicolon_statements {
+            true => split_semicolons(iter, &mut errors),
+            false => vec![iter],
+        };
+        for part in parts {
+            match parse_line(&mut part.into_iter().peekable(), &mut errors, config) {
+                Ok(Some(mut line)) => {
+                    line.offset = of;
+                    result.push((of, line))
+                }
+                Ok(None) => {}
+                Err(e) => errors.push(e),
+            }
         }
     }
 
+    let (warnings, errors): (Vec<_>, Vec<_>) = errors
+        .into_iter()
+        .partition(|e| e.severity() == Severity::Warning);
     if !errors.is_empty() {
         return Err(errors);
     }
-    Ok(result)
+    Ok((result, warnings))
+}
+
+// Splits one token line at its top-level `;` into several
+//     statements at the same offset. The empty tail of a
+//     trailing `;` is dropped; a leading `;` is an error.
+fn split_semicolons(
+    tokens: Vec<(Token, Span)>,
+    errors: &mut Vec<Error>,
+) -> Vec<Vec<(Token, Span)>> {
+    let mut parts = Vec::new();
+    let mut current = Vec::new();
+    let mut depth = 0usize;
+    for (token, span) in tokens {
+        match &token {
+            Token::Bracket(_, true) => depth += 1,
+            Token::Bracket(_, false) => depth = depth.saturating_sub(1),
+            Token::Special(s) if depth == 0 && *s == ";".into() => {
+                let statement = current.iter().any(|(t, _)| !matches!(t, Token::Whitespace(_)));
+                match statement {
+                    true => parts.push(std::mem::take(&mut current)),
+                    false => errors.push(Box::new(UnexpectedSymbol::new(span, ';'))),
+                }
+                continue;
+            }
+            _ => {}
+        }
+        current.push((token, span))
+    }
+    if current.iter().any(|(t, _)| !matches!(t, Token::Whitespace(_))) {
+        parts.push(current)
+    }
+    parts
 }
 
 type Tokens<'a> = Peekable<std::vec::IntoIter<(Token, Span)>>;
 
-pub fn parse_line(tokens: &mut Tokens) -> Result<Option<Line>, Error> {
+pub fn parse_line(
+    tokens: &mut Tokens,
+    errors: &mut Vec<Error>,
+    config: &ParseConfig,
+) -> Result<Option<Line>, Error> {
     let mut sent = Vec::new();
+    // A literal may begin at the start of the statement or right
+    //     after an operator; whitespace doesn't move the position.
+    let mut expect_literal = true;
     while let Some((token, span)) = tokens.next() {
-        sent.push(parse_expr(tokens, token, span)?)
+        match parse_expr(tokens, token, span, errors, config, 0, expect_literal) {
+            Ok(expr) => {
+                if let Some(expr) = &expr {
+                    expect_literal = matches!(expr.expr, ExprT::Special(_))
+                }
+                sent.push(expr)
+            }
+            Err(e) if config.collect_errors => {
+                errors.push(e);
+                sent.push(Some(Expr::new_e(span)));
+                resynchronize(tokens);
+            }
+            Err(e) => return Err(e),
+        }
     }
     match Sent::new(sent.into_iter().flatten().collect()) {
         Some(sent) => Ok(Some(Line::new(sent))),
@@ -73,26 +240,94 @@ pub fn parse_line(tokens: &mut Tokens) -> Result<Option<Line>, Error> {
     }
 }
 
-fn parse_expr(tokens: &mut Tokens, token: Token, span: Span) -> Result<Option<Expr>, Error> {
+// Skips tokens up to the next statement boundary,
+//     so several errors can be reported for one line.
+fn resynchronize(tokens: &mut Tokens) {
+    while let Some((token, _)) = tokens.peek() {
+        match token {
+            Token::Whitespace(_) | Token::Comma | Token::Bracket(_, false) => return,
+            _ => {
+                tokens.next().unwrap();
+            }
+        }
+    }
+}
+
+fn parse_expr(
+    tokens: &mut Tokens,
+    token: Token,
+    span: Span,
+    errors: &mut Vec<Error>,
+    config: &ParseConfig,
+    depth: usize,
+    expect_literal: bool,
+) -> Result<Option<Expr>, Error> {
     Ok(match token {
         Token::Comma => raise_error!(UnexpectedSymbol, span, ','),
+        // `;` only separates statements (`semicolon_statements`) -
+        //     anywhere else it's not a symbol of the language.
+        Token::Special(s) if s == ";".into() => raise_error!(UnexpectedSymbol, span, ';'),
+        // A configured `comment_marker` comments out the rest of
+        //     the line, like `. ` does.
+        Token::Special(s)
+            if config.comment_marker.map_or(false, |m| m.starts_with(&s.to_string())) =>
+        {
+            let marker = config.comment_marker.unwrap();
+            match take_comment_marker(tokens, span, &s.to_string(), marker) {
+                Some(from) if config.keep_comments => Some(comment(tokens, from)),
+                Some(_) => {
+                    while let Some(_) = tokens.next() {}
+                    None
+                }
+                None => Some(Expr::new_s(s, span)),
+            }
+        }
         Token::Bracket(_, false) => raise_error!(ClosedBracket, span,),
-        Token::Dot => parse_inner(tokens, span)?,
-        Token::Word(w) => Some(parse_chain(tokens, w, span)?),
-        Token::Bracket(bt, true) => Some(parse_bracket(tokens, bt, span)?),
+        Token::Dot => parse_inner(tokens, span, config)?,
+        Token::Word(w) => Some(parse_chain(tokens, w, span, config)?),
+        Token::Bracket(bt, true) => Some(parse_bracket(tokens, bt, span, errors, config, depth)?),
+        // "-" immediately followed by a number is a negative literal,
+        //     but only where a literal may begin - at the start of a
+        //     statement or part, or right after another operator.
+        //     Glued to a preceding value (`a-5`) it stays a binary
+        //     operator, like with separating whitespace.
+        Token::Special(s) if s == "-".into() && expect_literal => {
+            match tokens.peek().map(|t| t.clone()) {
+                Some((Token::LitInt(li, radix, suffix), s2)) if span.end() == s2.begin() => {
+                    tokens.next().unwrap();
+                    Some(Expr::new_li(-li, radix, suffix, span + s2))
+                }
+                Some((Token::LitFloat(lf, suffix), s2)) if span.end() == s2.begin() => {
+                    tokens.next().unwrap();
+                    Some(Expr::new_lf(-lf, suffix, span + s2))
+                }
+                _ => Some(Expr::new_s(s, span)),
+            }
+        }
         Token::Special(s) => Some(Expr::new_s(s, span)),
-        Token::LitInt(li) => Some(Expr::new_li(li, span)),
+        Token::LitInt(li, radix, suffix) => Some(Expr::new_li(li, radix, suffix, span)),
+        Token::LitFloat(lf, suffix) => Some(Expr::new_lf(lf, suffix, span)),
+        Token::LitChar(lc) => Some(Expr::new_lc(lc, span)),
         Token::LitStr(ls) => Some(Expr::new_ls(ls, span)),
         _ => None,
     })
 }
 
-fn parse_inner(tokens: &mut Tokens, begin: Span) -> Result<Option<Expr>, Error> {
+fn parse_inner(
+    tokens: &mut Tokens,
+    begin: Span,
+    config: &ParseConfig,
+) -> Result<Option<Expr>, Error> {
     match tokens.next() {
-        Some((Token::Whitespace(1), _)) => {
-            while let Some(_) = tokens.next() {} // `. ` is a comment - drain iterator.
+        Some((Token::Whitespace(1), s)) => {
+            // `. ` is a comment - drain iterator.
+            if config.keep_comments {
+                return Ok(Some(comment(tokens, begin + s)));
+            }
+            while let Some(_) = tokens.next() {}
             Ok(None)
         }
+        Some((Token::Dot, s)) => Ok(Some(doc_comment(tokens, begin + s))),
         Some((Token::Word(w), s)) => Ok(Some(Expr::new_i(
             Box::new(Expr::new_c(vec![w], s)),
             begin + s,
@@ -102,7 +337,98 @@ fn parse_inner(tokens: &mut Tokens, begin: Span) -> Result<Option<Expr>, Error>
     }
 }
 
-fn parse_chain(tokens: &mut Tokens, first: Symbol, from: Span) -> Result<Expr, Error> {
+// A configured line-comment marker may lex as several adjacent
+//     `Special` tokens (`//` is two `/`): they are consumed
+//     greedily while they extend the marker.
+// To be done: don't consume on a partial match of markers
+//     longer than two tokens.
+fn take_comment_marker(tokens: &mut Tokens, span: Span, first: &str, marker: &str) -> Option<Span> {
+    let mut matched = String::from(first);
+    let mut end = span;
+    while matched != marker {
+        match tokens.peek() {
+            Some((Token::Special(s), next))
+                if end.end() == next.begin()
+                    && marker[matched.len()..].starts_with(&s.to_string()) =>
+            {
+                matched.push_str(&s.to_string());
+                end = *next;
+                tokens.next().unwrap();
+            }
+            _ => return None,
+        }
+    }
+    Some(span + end)
+}
+
+// `..` is a doc comment: its text is kept, markers and one
+//     separating space are stripped.
+fn doc_comment(tokens: &mut Tokens, from: Span) -> Expr {
+    let (text, span) = comment_text(tokens, from);
+    Expr::new_dc(text, span)
+}
+
+// `. ` comment kept as trivia with `ParseConfig::keep_comments`.
+fn comment(tokens: &mut Tokens, from: Span) -> Expr {
+    let (text, span) = comment_text(tokens, from);
+    Expr::new_cm(text, span)
+}
+
+fn comment_text(tokens: &mut Tokens, from: Span) -> (String, Span) {
+    let mut to = from;
+    if let Some((Token::Whitespace(1), _)) = tokens.peek() {
+        tokens.next().unwrap();
+    }
+    let mut text = String::new();
+    for (token, span) in tokens {
+        to = span;
+        detokenize(&mut text, token);
+    }
+    (text, from + to)
+}
+
+// Restores source text from a token.
+// To be done: slice the source by `Span` instead.
+fn detokenize(text: &mut String, token: Token) {
+    match token {
+        Token::Dot => text.push('.'),
+        Token::Comma => text.push(','),
+        Token::NewLine => text.push('\n'),
+        Token::Comment => {}
+        Token::Bracket(bt, open) => text.push(match (bt, open) {
+            (BracketType::Round, true) => '(',
+            (BracketType::Round, false) => ')',
+            (BracketType::Square, true) => '[',
+            (BracketType::Square, false) => ']',
+            (BracketType::Curly, true) => '{',
+            (BracketType::Curly, false) => '}',
+        }),
+        Token::Whitespace(w) => text.extend(std::iter::repeat(' ').take(w)),
+        Token::Tabulation(t) => text.extend(std::iter::repeat('\t').take(t)),
+        Token::Special(s) | Token::Word(s) => text.push_str(&s.to_string()),
+        Token::LitInt(i, _, suffix) => {
+            text.push_str(&i.to_string());
+            if let Some(s) = suffix {
+                text.push_str(&s.to_string())
+            }
+        }
+        Token::LitFloat(f, suffix) => {
+            text.push_str(&f.to_string());
+            if let Some(s) = suffix {
+                text.push_str(&s.to_string())
+            }
+        }
+        Token::LitStr(s) => text.push_str(&format!("{:?}", s)),
+        Token::LitChar(c) => text.push_str(&format!("{:?}", c)),
+    }
+}
+
+fn parse_chain(
+    tokens: &mut Tokens,
+    first: Symbol,
+    from: Span,
+    config: &ParseConfig,
+) -> Result<Expr, Error> {
     let mut chain = vec![first];
     let mut to = from;
     while let Some((Token::Dot, _)) = tokens.peek() {
@@ -113,42 +439,678 @@ fn parse_chain(tokens: &mut Tokens, first: Symbol, from: Span) -> Result<Expr, E
                 w
             }
             Some((_, span)) => raise_error!(UnexpectedToken, span,),
-            None => raise_error!(UnexpectedEndOfLine, from + span,),
+            // Trailing dot: the error points at the dot itself.
+            None => raise_error!(UnexpectedEndOfLine, span,),
         })
     }
-    Ok(Expr::new_c(chain, from + to))
+    let is_keyword = |w: &Symbol| config.keywords.contains(&w.to_string().as_str());
+    match &chain[..] {
+        // A reserved word is its own classification...
+        [word] if is_keyword(word) => match word.to_string().as_str() {
+            // ...and `true`/`false` among the keywords are literals.
+            "true" => Ok(Expr::new_lb(true, from + to)),
+            "false" => Ok(Expr::new_lb(false, from + to)),
+            _ => Ok(Expr::new_k(*word, from + to)),
+        },
+        // ...and never part of an identifier chain.
+        words if words.iter().any(is_keyword) => {
+            raise_error!(UnexpectedToken, from + to,)
+        }
+        _ => Ok(Expr::new_c(chain, from + to)),
+    }
 }
 
-fn parse_bracket(tokens: &mut Tokens, bt: BracketType, from: Span) -> Result<Expr, Error> {
-    let mut to = from;
-    let mut expr = Vec::new();
-    let mut sent = Vec::new();
+// One still-open bracket of `parse_bracket`.
+struct Frame {
+    bt: BracketType,
+    from: Span,
+    to: Span,
+    parts: Vec<Sent>,
+    sent: Vec<Expr>,
+}
+
+impl Frame {
+    fn new(bt: BracketType, from: Span) -> Self {
+        Self {
+            bt,
+            from,
+            to: from,
+            parts: Vec::new(),
+            sent: Vec::new(),
+        }
+    }
+}
+
+// Nested brackets are handled with an explicit stack of open
+//     frames instead of recursion, so their depth is bounded by
+//     memory, not by the call stack.
+fn parse_bracket(
+    tokens: &mut Tokens,
+    bt: BracketType,
+    from: Span,
+    errors: &mut Vec<Error>,
+    config: &ParseConfig,
+    depth: usize,
+) -> Result<Expr, Error> {
+    if depth >= config.max_depth {
+        raise_error!(NestingTooDeep, from, config.max_depth)
+    }
+    let mut stack = vec![Frame::new(bt, from)];
+    // A literal may begin right after the opening bracket, a comma
+    //     or an operator - mirrors the tracking of `parse_line`.
+    let mut expect_literal = true;
     while let Some((token, span)) = tokens.next() {
-        to = span;
-        sent.push(match token {
+        stack.last_mut().unwrap().to = span;
+        match token {
             Token::Comma => {
-                expr.push(match Sent::new(sent) {
-                    Some(next) => next,
-                    None => raise_error!(EmptyPartInBrackets, from + to,),
-                });
-                sent = Vec::new();
-                continue;
+                expect_literal = true;
+                let frame = stack.last_mut().unwrap();
+                match Sent::new(std::mem::take(&mut frame.sent)) {
+                    Some(next) => frame.parts.push(next),
+                    // Zero-width at the comma: the gap is the error,
+                    //     not everything since the open bracket.
+                    None => raise_error!(
+                        EmptyPartBeforeComma,
+                        Span::new(span.begin(), span.begin()),
+                    ),
+                }
+            }
+            Token::Bracket(t, true) => {
+                if depth + stack.len() >= config.max_depth {
+                    raise_error!(NestingTooDeep, span, config.max_depth)
+                }
+                expect_literal = true;
+                stack.push(Frame::new(t, span))
+            }
+            Token::Bracket(t, false) => {
+                let frame = stack.last().unwrap();
+                if t != frame.bt {
+                    // Wrong close: point at the still-open bracket too.
+                    raise_error!(
+                        MismatchedBracket,
+                        span,
+                        frame.bt.close_char(),
+                        frame.bt.open_char(),
+                        frame.from
+                    )
+                }
+                let mut done = stack.pop().unwrap();
+                if !done.parts.is_empty() && done.sent.is_empty() {
+                    let gap = Span::new(span.begin(), span.begin());
+                    if !config.allow_trailing_comma {
+                        raise_error!(EmptyPartBeforeComma, gap,)
+                    }
+                    errors.push(Box::new(TrailingComma::new(gap)))
+                }
+                if let Some(next) = Sent::new(done.sent) {
+                    done.parts.push(next)
+                }
+                let expr = Expr::new_b(done.bt, done.parts, done.from + span);
+                expect_literal = false;
+                match stack.last_mut() {
+                    Some(parent) => parent.sent.push(expr),
+                    None => return Ok(expr),
+                }
             }
-            Token::Bracket(t, false) if t == bt => {
-                if !expr.is_empty() && sent.is_empty() {
-                    raise_error!(EmptyPartInBrackets, from + to,)
+            token => {
+                let depth = depth + stack.len();
+                let expr = parse_expr(tokens, token, span, errors, config, depth, expect_literal)?;
+                if let Some(next) = expr {
+                    expect_literal = matches!(next.expr, ExprT::Special(_));
+                    stack.last_mut().unwrap().sent.push(next)
                 }
-                match Sent::new(sent) {
-                    Some(next) => expr.push(next),
-                    None => {}
+            }
+        }
+    }
+    // Several brackets may be open at EOF - the innermost one
+    //     errors, exactly as the recursive version unwound.
+    let frame = stack.last().unwrap();
+    raise_error!(ClosingBracketNotFound, frame.from + frame.to,)
+}
+
+// Whether every error could be fixed by more input - an open
+//     bracket, an unterminated string, a trailing continuation.
+pub(crate) fn recoverable_at_eof(errors: &[Error]) -> bool {
+    errors.iter().any(|e| {
+        matches!(
+            e.kind(),
+            ErrorKind::UnexpectedEOS
+                | ErrorKind::ClosingBracketNotFound
+                | ErrorKind::NewLineOnFileEnd
+        )
+    })
+}
+
+/// Iterator of `parse_lines`: one parsed line at a time.
+pub struct ParseLines<'a> {
+    rest: &'a str,
+    /// Char offset of `rest` within the whole source.
+    base: usize,
+    config: &'a ParseConfig,
+    pending: vec::IntoIter<(usize, Line)>,
+}
+
+impl<'a> ParseLines<'a> {
+    pub(crate) fn new(code: &'a str, config: &'a ParseConfig) -> Self {
+        Self {
+            rest: code,
+            base: 0,
+            config,
+            pending: Vec::new().into_iter(),
+        }
+    }
+
+    fn consume(&mut self, bytes: usize) {
+        let (fragment, rest) = self.rest.split_at(bytes);
+        self.base += fragment.chars().count();
+        self.rest = rest;
+    }
+}
+
+impl Iterator for ParseLines<'_> {
+    type Item = Result<(usize, Line), Vec<Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((offset, line)) = self.pending.next() {
+                return Some(Ok((offset, line)));
+            }
+            if self.rest.is_empty() {
+                return None;
+            }
+            // Physical lines are taken one by one until the
+            //     fragment parses, or fails in a way more input
+            //     can't fix, or the source runs out.
+            let mut end = 0;
+            let (fragment, result) = loop {
+                end = match self.rest[end..].find('\n') {
+                    Some(i) => end + i + 1,
+                    None => self.rest.len(),
                 };
-                return Ok(Expr::new_b(bt, expr, from + to));
+                let fragment = &self.rest[..end];
+                match parse(fragment, self.config) {
+                    Err(errors) if end < self.rest.len() && recoverable_at_eof(&errors) => {}
+                    result => break (fragment, result),
+                }
+            };
+            let base = self.base as isize;
+            self.consume(fragment.len());
+            match result {
+                // Warnings of the fragment are dropped - use the
+                //     whole-file `parse` to collect them.
+                Ok((mut lines, _)) => {
+                    for (_, line) in &mut lines {
+                        line.shift_positions(0, base)
+                    }
+                    self.pending = lines.into_iter();
+                }
+                Err(errors) => {
+                    let shift = |e| Box::new(OffsetError::new(e, base as usize)) as Error;
+                    return Some(Err(errors.into_iter().map(shift).collect()));
+                }
             }
-            _ => match parse_expr(tokens, token, span)? {
-                Some(next) => next,
-                None => continue,
-            },
-        })
+        }
+    }
+}
+
+// An error from a fragment parse, shifted to whole-file offsets.
+#[derive(derive_new::new, Debug)]
+struct OffsetError {
+    inner: Error,
+    delta: usize,
+}
+
+impl crate::common::location::HasSpan for OffsetError {
+    fn span(&self) -> Span {
+        let span = self.inner.span();
+        Span::new(
+            span.begin().advanced(self.delta),
+            span.end().advanced(self.delta),
+        )
+    }
+}
+
+impl crate::common::error::IsError for OffsetError {
+    fn message(&self) -> String {
+        self.inner.message()
+    }
+
+    fn kind(&self) -> ErrorKind {
+        self.inner.kind()
+    }
+
+    fn severity(&self) -> Severity {
+        self.inner.severity()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::error::ErrorKind;
+    use crate::parser::ast::ExprT;
+
+    #[test]
+    fn serde_round_trip() {
+        let config = Default::default();
+        let (parsed, _) = parse("let a 3 (b, c.d \"s\")\n", &config).unwrap();
+        let json = serde_json::to_string(&parsed).unwrap();
+        let restored: Vec<(usize, Line)> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, restored);
+    }
+
+    // 100k openers must fail cleanly, not overflow the stack.
+    #[test]
+    fn nesting_limit() {
+        let config = ParseConfig::default();
+        let source = format!("{}\n", "(".repeat(100_000));
+        let errors = parse(&source, &config).unwrap_err();
+        assert_eq!(errors[0].kind(), ErrorKind::NestingTooDeep);
+        // The error points at the first opener past the limit.
+        assert_eq!(errors[0].span().begin().as_usize(), config.max_depth);
+        // The limit counts nesting, not brackets in sequence.
+        assert!(parse("f (a) (b) (c)\n", &config).is_ok());
+    }
+
+    // Bracket parsing runs on an explicit stack: with the limit
+    //     raised, depth is bounded by memory, not the call stack.
+    #[test]
+    fn deep_nesting_iterative() {
+        let config = ParseConfig {
+            max_depth: usize::MAX,
+            ..Default::default()
+        };
+        let depth = 5_000;
+        let source = format!("{}a{}\n", "(".repeat(depth), ")".repeat(depth));
+        let (parsed, _) = parse(&source, &config).unwrap();
+        let mut expr = &parsed[0].1.sent.sent[0];
+        for _ in 0..depth {
+            expr = match &expr.expr {
+                ExprT::Bracket(BracketType::Round, parts) => &parts[0].sent[0],
+                other => panic!("expected a bracket, got {:?}", other),
+            }
+        }
+        assert!(matches!(expr.expr, ExprT::Chain(_)));
+    }
+
+    #[test]
+    fn multi_line_bracket() {
+        let config = Default::default();
+        let (parsed, _) = parse("f (a,\n   b)\n", &config).unwrap();
+        assert_eq!(parsed.len(), 1);
+        match &parsed[0].1.sent.sent[1].expr {
+            ExprT::Bracket(_, parts) => assert_eq!(parts.len(), 2),
+            expr => panic!("expected a bracket, got {:?}", expr),
+        }
+        // Still open at EOF - reported at the opening bracket.
+        assert!(parse("f (a\n", &config).is_err());
+    }
+
+    // Each part's span must exactly cover its source text,
+    //     also for brackets appearing mid-line.
+    #[test]
+    fn bracket_part_spans() {
+        let config = Default::default();
+        let source = "f (ab, cde)\n";
+        let (parsed, _) = parse(source, &config).unwrap();
+        match &parsed[0].1.sent.sent[1].expr {
+            ExprT::Bracket(_, parts) => {
+                let text = |s: &Sent| {
+                    source[s.span.begin().as_usize()..s.span.end().as_usize()].to_string()
+                };
+                assert_eq!(text(&parts[0]), "ab");
+                assert_eq!(text(&parts[1]), "cde");
+            }
+            expr => panic!("expected a bracket, got {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn empty_bracket_rules() {
+        let config = ParseConfig::default();
+        // `()` is an empty collection.
+        let (parsed, _) = parse("f ()\n", &config).unwrap();
+        match &parsed[0].1.sent.sent[1].expr {
+            ExprT::Bracket(_, parts) => assert!(parts.is_empty()),
+            expr => panic!("expected a bracket, got {:?}", expr),
+        }
+        // A leading comma is always an error.
+        assert!(parse("f (,a)\n", &config).is_err());
+        // A trailing comma is opt-in.
+        assert!(parse("f (a,)\n", &config).is_err());
+        let relaxed = ParseConfig {
+            allow_trailing_comma: true,
+            ..config
+        };
+        let (parsed, _) = parse("f (a,)\n", &relaxed).unwrap();
+        match &parsed[0].1.sent.sent[1].expr {
+            ExprT::Bracket(_, parts) => assert_eq!(parts.len(), 1),
+            expr => panic!("expected a bracket, got {:?}", expr),
+        }
+    }
+
+    // A whitespace-only tail never extends the line's span:
+    //     whitespace tokens produce no `Expr` and `Sent::new`
+    //     merges only real expression spans.
+    #[test]
+    fn trailing_whitespace_spans() {
+        let config = Default::default();
+        let (parsed, _) = parse("f xy   \n", &config).unwrap();
+        let line = &parsed[0].1;
+        assert_eq!(line.span.end().as_usize(), 4);
+        let (stripped, _) = parse("f xy\n", &config).unwrap();
+        assert_eq!(stripped[0].1.span, line.span);
+        assert_eq!(stripped[0].1.sent, line.sent);
+    }
+
+    // Malformed input - stray newlines, unterminated literals,
+    //     lone separators - must only ever produce `Error`s,
+    //     never a panic: the crate processes arbitrary user source.
+    #[test]
+    fn no_panic_on_malformed_input() {
+        let config = Default::default();
+        for source in [
+            "\n\n\n", "f\n\nx\n", "(\n)\n", "...\n", ".\n", "'\n", "\"\n", "a..b\n", ",\n",
+            ")\n", "f x", "  \n \t \n", "f\u{0}x\n",
+        ] {
+            let _ = parse(source, &config);
+        }
+    }
+
+    // Offsets are file-wide and never byte-sized: a line longer
+    //     than 255 chars keeps exact spans.
+    #[test]
+    fn long_line_spans() {
+        let config = Default::default();
+        let source = format!("f {}\n", "a".repeat(298));
+        let (parsed, _) = parse(&source, &config).unwrap();
+        let line = &parsed[0].1;
+        assert_eq!(line.span.begin().as_usize(), 0);
+        assert_eq!(line.span.end().as_usize(), 300);
+    }
+
+    // The gap errors are zero-width at the comma (or closing
+    //     bracket), not everything parsed so far.
+    #[test]
+    fn empty_part_spans() {
+        let config = ParseConfig::default();
+        let check = |src: &str, at: usize| {
+            let errors = parse(src, &config).unwrap_err();
+            assert_eq!(errors[0].kind(), ErrorKind::EmptyPartBeforeComma, "{:?}", src);
+            assert_eq!(errors[0].span().begin().as_usize(), at, "{:?}", src);
+            assert_eq!(errors[0].span().end().as_usize(), at, "{:?}", src);
+        };
+        check("f (,)\n", 3);
+        check("f (a,,b)\n", 5);
+        check("f (a,)\n", 5);
+    }
+
+    #[test]
+    fn backslash_continuation() {
+        let config = Default::default();
+        // One statement across three physical lines.
+        let (parsed, _) = parse("a \\\nb \\\nc\n", &config).unwrap();
+        assert_eq!(parsed.len(), 1);
+        let sent = &parsed[0].1.sent.sent;
+        assert_eq!(sent.len(), 3);
+        // Spans still index the original source.
+        assert_eq!(sent[2].span.begin().as_usize(), 8);
+        // Continuation indentation isn't a new offset.
+        let (parsed, _) = parse("f x \\\n  y\n", &config).unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].1.sent.sent.len(), 3);
+        // A backslash anywhere else isn't a continuation.
+        assert!(parse("a \\ b\n", &config).is_err());
+        // Invisible blanks after the backslash don't break it.
+        let (parsed, _) = parse("a \\ \nb\n", &config).unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].1.sent.sent.len(), 2);
+        // Joining sees tokens, not text: a backslash before the
+        //     break inside a raw string is content...
+        let (parsed, _) = parse("f r\"a\\\nb\"\n", &config).unwrap();
+        assert_eq!(parsed.len(), 1);
+        let sent = &parsed[0].1.sent.sent;
+        assert!(matches!(&sent[1].expr, ExprT::LitStr(s) if s == "a\\\nb"));
+        // ...and in a triple-quoted string it's a bad escape, not
+        //     two silent spaces.
+        assert!(parse("f \"\"\"a\\\nb\"\"\"\n", &config).is_err());
+    }
+
+    #[test]
+    fn semicolon_statements() {
+        let config = ParseConfig {
+            semicolon_statements: true,
+            ..Default::default()
+        };
+        let (parsed, _) = parse("a; b\n", &config).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].0, parsed[1].0);
+        // Trailing `;` leaves no empty statement behind.
+        let (parsed, _) = parse("a;\n", &config).unwrap();
+        assert_eq!(parsed.len(), 1);
+        // Leading `;` has nothing to terminate.
+        assert!(parse("; a\n", &config).is_err());
+        // Off by default, `;` isn't part of the language.
+        match parse("a; b\n", &Default::default()) {
+            Err(errors) => assert_eq!(errors[0].kind(), ErrorKind::UnexpectedSymbol),
+            Ok(_) => panic!("`;` parsed without the config"),
+        }
+    }
+
+    #[test]
+    fn comment_markers() {
+        let hash = ParseConfig {
+            comment_marker: Some("#"),
+            ..Default::default()
+        };
+        let slash = ParseConfig {
+            comment_marker: Some("//"),
+            ..Default::default()
+        };
+        // The same source under either marker: everything after
+        //     the marker is dropped.
+        let (parsed, _) = parse("f x # y z\n", &hash).unwrap();
+        assert_eq!(parsed[0].1.sent.sent.len(), 2);
+        let (parsed, _) = parse("f x // y z\n", &slash).unwrap();
+        assert_eq!(parsed[0].1.sent.sent.len(), 2);
+        // A marker-only line parses to nothing, like `. ` does.
+        let (parsed, _) = parse("# y z\n", &hash).unwrap();
+        assert!(parsed.is_empty());
+        // Half of `//` is an ordinary special token.
+        let (parsed, _) = parse("a / b\n", &slash).unwrap();
+        assert_eq!(parsed[0].1.sent.sent.len(), 3);
+        // With `keep_comments` the text survives as trivia.
+        let keep = ParseConfig {
+            keep_comments: true,
+            ..hash
+        };
+        let (parsed, _) = parse("f # kept\n", &keep).unwrap();
+        let sent = &parsed[0].1.sent.sent;
+        assert!(matches!(&sent[1].expr, ExprT::Comment(text) if text == "kept"));
+    }
+
+    #[test]
+    fn dotted_chains() {
+        let config = Default::default();
+        let (parsed, _) = parse("a.b.c\n", &config).unwrap();
+        let sent = &parsed[0].1.sent.sent;
+        assert_eq!(sent.len(), 1);
+        match &sent[0].expr {
+            ExprT::Chain(chain) => assert_eq!(chain.len(), 3),
+            other => panic!("not a chain: {:?}", other),
+        }
+        assert_eq!(sent[0].span.end().as_usize(), 5);
+        // A dot with nothing after it errors on the dot.
+        let errors = parse("foo.\n", &config).unwrap_err();
+        assert_eq!(errors[0].span().begin().as_usize(), 3);
+        assert_eq!(errors[0].span().end().as_usize(), 4);
+        // Dots in numbers stay literals.
+        let (parsed, _) = parse("3.14\n", &config).unwrap();
+        assert!(matches!(parsed[0].1.sent.sent[0].expr, ExprT::LitFloat(..)));
+    }
+
+    #[test]
+    fn keyword_classification() {
+        let config = ParseConfig {
+            keywords: &["let", "if"],
+            ..Default::default()
+        };
+        let (parsed, _) = parse("let x\n", &config).unwrap();
+        let sent = &parsed[0].1.sent.sent;
+        assert!(matches!(sent[0].expr, ExprT::Keyword(_)));
+        assert!(matches!(sent[1].expr, ExprT::Chain(_)));
+        // A keyword is reserved - not a name within a chain.
+        assert!(parse("a.let\n", &config).is_err());
+        assert!(parse("let.x\n", &config).is_err());
+        // Without the config entry `let` is an ordinary word.
+        let (parsed, _) = parse("let x\n", &Default::default()).unwrap();
+        assert!(matches!(parsed[0].1.sent.sent[0].expr, ExprT::Chain(_)));
+    }
+
+    #[test]
+    fn free_form_mode() {
+        // Ragged indentation fits no unit in strict mode.
+        assert!(parse("f\n   g\n     h\n", &Default::default()).is_err());
+        let free = ParseConfig {
+            free_form: true,
+            ..Default::default()
+        };
+        let (parsed, _) = parse("f\n   g\n\t h\n", &free).unwrap();
+        assert_eq!(parsed.len(), 3);
+        // Everything sits at offset zero - the tree stays flat.
+        assert!(parsed.iter().all(|(of, _)| *of == 0));
+    }
+
+    // Part splitting sees tokens, not chars: a comma inside a
+    //     string literal or a nested bracket never splits.
+    #[test]
+    fn comma_inside_string_or_bracket() {
+        let config = Default::default();
+        let (parsed, _) = parse("f (\"a,b\", c)\n", &config).unwrap();
+        let parts = match &parsed[0].1.sent.sent[1].expr {
+            ExprT::Bracket(_, parts) => parts,
+            other => panic!("not a bracket: {:?}", other),
+        };
+        assert_eq!(parts.len(), 2);
+        assert!(matches!(&parts[0].sent[0].expr, ExprT::LitStr(s) if s == "a,b"));
+
+        let (parsed, _) = parse("f ((a, b), c)\n", &config).unwrap();
+        let parts = match &parsed[0].1.sent.sent[1].expr {
+            ExprT::Bracket(_, parts) => parts,
+            other => panic!("not a bracket: {:?}", other),
+        };
+        assert_eq!(parts.len(), 2);
+        assert!(matches!(&parts[0].sent[0].expr, ExprT::Bracket(_, inner) if inner.len() == 2));
+    }
+
+    #[test]
+    fn bool_literals() {
+        let config = ParseConfig {
+            keywords: &["true", "false", "if"],
+            ..Default::default()
+        };
+        let (parsed, _) = parse("true false\n", &config).unwrap();
+        let sent = &parsed[0].1.sent.sent;
+        assert!(matches!(sent[0].expr, ExprT::LitBool(true)));
+        assert!(matches!(sent[1].expr, ExprT::LitBool(false)));
+        // Still reserved like any keyword.
+        assert!(parse("a.true\n", &config).is_err());
+        // A longer word is an ordinary identifier.
+        let (parsed, _) = parse("trueish\n", &config).unwrap();
+        assert!(matches!(parsed[0].1.sent.sent[0].expr, ExprT::Chain(_)));
+        // Without the config entries `true` is one too.
+        let (parsed, _) = parse("true\n", &Default::default()).unwrap();
+        assert!(matches!(parsed[0].1.sent.sent[0].expr, ExprT::Chain(_)));
+    }
+
+    // Positions used to be stored in a byte, silently wrapping
+    //     past offset 255.
+    #[test]
+    fn line_longer_than_255() {
+        let config = Default::default();
+        let source = format!("f {}\n", "a".repeat(998));
+        let (parsed, _) = parse(&source, &config).unwrap();
+        let line = &parsed[0].1;
+        assert_eq!(line.span.end().as_usize(), 1000);
+        let word = line.sent.sent.last().unwrap();
+        assert_eq!(word.span.begin().as_usize(), 2);
+        assert_eq!(word.span.end().as_usize(), 1000);
+    }
+
+    #[test]
+    fn warnings_accumulate() {
+        let relaxed = ParseConfig {
+            allow_trailing_comma: true,
+            ..Default::default()
+        };
+        let (parsed, warnings) = parse("f (a,)\n", &relaxed).unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind(), ErrorKind::TrailingComma);
+        assert_eq!(warnings[0].severity(), Severity::Warning);
+
+        let (_, warnings) = parse("f x\n\tg y\n", &Default::default()).unwrap();
+        assert_eq!(warnings[0].kind(), ErrorKind::TabIndentation);
+    }
+
+    #[test]
+    fn line_length_limit() {
+        let config = ParseConfig {
+            max_line_length: Some(10),
+            ..Default::default()
+        };
+        let (parsed, warnings) = parse("f x\ng aaaaaaaaaaaa\n", &config).unwrap();
+        assert_eq!(parsed.len(), 2);
+        // Exactly one warning, for the one line over the limit.
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind(), ErrorKind::LineTooLong);
+        assert_eq!(warnings[0].severity(), Severity::Warning);
+        // The span covers only the overflow.
+        assert_eq!(warnings[0].span().begin().as_usize(), 14);
+        assert_eq!(warnings[0].span().end().as_usize(), 18);
+        // Off by default.
+        let (_, warnings) = parse("f aaaaaaaaaaaaaaaaaa\n", &Default::default()).unwrap();
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn mismatched_close() {
+        let config = Default::default();
+        let errors = parse("f [a)\n", &config).unwrap_err();
+        assert!(errors[0].message().contains("expected `]` to close `[`"));
+    }
+
+    #[test]
+    fn negative_literals() {
+        let config = Default::default();
+        let (parsed, _) = parse("-5\n", &config).unwrap();
+        let sent = &parsed[0].1.sent.sent;
+        assert!(matches!(sent[0].expr, ExprT::LitInt(-5, _, _)));
+
+        let (parsed, _) = parse("a - 5\n", &config).unwrap();
+        let sent = &parsed[0].1.sent.sent;
+        assert!(matches!(sent[1].expr, ExprT::Special(_)));
+        assert!(matches!(sent[2].expr, ExprT::LitInt(5, _, _)));
+
+        // Glued to a preceding value the "-" is subtraction too.
+        let (parsed, _) = parse("a-5\n", &config).unwrap();
+        let sent = &parsed[0].1.sent.sent;
+        assert_eq!(sent.len(), 3);
+        assert!(matches!(sent[1].expr, ExprT::Special(_)));
+        assert!(matches!(sent[2].expr, ExprT::LitInt(5, _, _)));
+
+        // After a comma or an opening bracket a literal may begin.
+        let (parsed, _) = parse("f (-1, -2)\n", &config).unwrap();
+        match &parsed[0].1.sent.sent[1].expr {
+            ExprT::Bracket(_, parts) => {
+                assert!(matches!(parts[0].sent[0].expr, ExprT::LitInt(-1, _, _)));
+                assert!(matches!(parts[1].sent[0].expr, ExprT::LitInt(-2, _, _)));
+            }
+            other => panic!("expected a bracket, got {:?}", other),
+        }
+
+        // After an operator as well: `= -5` keeps the sign.
+        let (parsed, _) = parse("x = -5\n", &config).unwrap();
+        assert!(matches!(parsed[0].1.sent.sent[2].expr, ExprT::LitInt(-5, _, _)));
     }
-    raise_error!(ClosingBracketNotFound, from + to,)
 }
@@ -1,20 +1,712 @@
 // Basic definitions.
 pub mod ast;
-mod errors;
+pub(crate) mod errors;
 mod symbol;
 // Parsing.
+mod highlight;
 mod lexer;
 mod lines;
+mod print;
 mod tree;
 
+pub use highlight::{highlight, highlight_with_config, TokenClass};
+pub use lines::ParseLines;
+pub use lexer::{Lexer, Token};
+pub use symbol::{SymbolType, OPERATORS};
+pub use print::print;
+
+/// Classified tokens with their spans, without any parsing -
+///     enough for syntax highlighting. Unlexable chars are skipped;
+///     use `Lexer` directly to observe the errors.
+pub fn tokenize(code: &str) -> impl Iterator<Item = (Token, crate::common::location::Span)> + '_ {
+    Lexer::new(code).filter_map(|token| token.ok())
+}
+
 use crate::common::error::Error;
-use crate::common::location::{File, HasSpan};
+use crate::common::location::{File, HasSpan, Position, Span};
+
+use errors::ReadFailed;
+
+/// Tunable parsing behavior, threaded through all parsing layers.
+#[derive(Debug, Clone, Copy)]
+pub struct ParseConfig {
+    /// Number of spaces per indentation level.
+    pub indent_width: u8,
+    /// When set, one leading tab is one indentation level
+    ///     regardless of `indent_width`.
+    pub tab_indent: bool,
+    /// Free-form mode: leading whitespace is insignificant, any
+    ///     ragged indentation parses and every line becomes a
+    ///     root - no indentation tree is built. For embeddings
+    ///     that only want statements and brackets.
+    pub free_form: bool,
+    /// When set, the parser doesn't stop a line at its first error:
+    ///     it records the error, leaves `ExprT::Error` in place and
+    ///     resynchronizes at the next statement boundary.
+    pub collect_errors: bool,
+    /// When set, ". " comments are kept as `ExprT::Comment` trivia
+    ///     (for formatters), instead of being dropped.
+    pub keep_comments: bool,
+    /// When set, `(a,)` is the same as `(a)`. `()` is always an
+    ///     empty collection and a leading comma is always an error.
+    pub allow_trailing_comma: bool,
+    /// Reserved words, classified as `ExprT::Keyword` and never
+    ///     usable as identifiers. Empty by default - the crate
+    ///     isn't tied to one language.
+    pub keywords: &'static [&'static str],
+    /// When set, `;` at the top level of a line splits it into
+    ///     several lines at the same offset. A trailing `;` is
+    ///     allowed, a leading one is an error. `;` is always an
+    ///     error inside brackets.
+    pub semicolon_statements: bool,
+    /// Line-comment introducer, e.g. `"#"` or `"//"`: the marker
+    ///     and everything after it up to the end of the line is a
+    ///     comment, with the same `keep_comments` behavior as the
+    ///     built-in `. `. Where an expression may start, the marker
+    ///     shadows the operator reading of its leading char.
+    pub comment_marker: Option<&'static str>,
+    /// Physical lines longer than this many chars warn with
+    ///     `LineTooLong` over the overflowing tail - a style
+    ///     check, so parsing continues. Off by default.
+    pub max_line_length: Option<usize>,
+    /// Brackets nested deeper than this fail with `NestingTooDeep`
+    ///     at the offending opener. Bracket parsing is iterative,
+    ///     so this is a policy limit, not a safety one - raise it
+    ///     freely for machine-generated input.
+    pub max_depth: usize,
+}
+
+impl Default for ParseConfig {
+    fn default() -> Self {
+        Self {
+            indent_width: 2,
+            tab_indent: false,
+            free_form: false,
+            collect_errors: false,
+            keep_comments: false,
+            allow_trailing_comma: false,
+            keywords: &[],
+            semicolon_statements: false,
+            comment_marker: None,
+            max_line_length: None,
+            max_depth: 256,
+        }
+    }
+}
+
+/// Guesses the indentation unit of `src` from the leading
+///     whitespace of its indented lines: the largest of 8, 4
+///     and 2 that divides every indent. The result plugs into
+///     `ParseConfig::indent_width`.
+/// `None` when nothing is indented, some indent fits no
+///     candidate, or tabs indent a line - inconsistent input
+///     shouldn't silently pick a unit.
+pub fn detect_indent(src: &str) -> Option<u8> {
+    let mut indents = Vec::new();
+    for line in src.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if line.starts_with('\t') {
+            return None;
+        }
+        let width = line.chars().take_while(|&c| c == ' ').count();
+        if width > 0 {
+            indents.push(width)
+        }
+    }
+    if indents.is_empty() {
+        return None;
+    }
+    [8u8, 4, 2]
+        .into_iter()
+        .find(|&unit| indents.iter().all(|i| i % usize::from(unit) == 0))
+}
+
+/// Lexical classification of one physical source line -
+///     see `line_kinds`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    /// Nothing but whitespace.
+    Blank,
+    /// A `. ` comment, a configured `comment_marker` comment or
+    ///     the shebang, and nothing else - exactly the lines that
+    ///     parse to no statement. `..` doc comments are statements
+    ///     in the tree, so they count as `Code`.
+    CommentOnly,
+    Code,
+}
+
+/// Kind of every physical line of `src`, in order. Blank and
+///     comment-only lines never reach the parsed tree, so the
+///     tree alone can't tell them apart - formatters preserving
+///     blank-line grouping need the distinction even when the
+///     comments themselves are discarded.
+/// Purely lexical: a continuation line of a multi-line bracket
+///     classifies on its own text.
+pub fn line_kinds(src: &str, config: &ParseConfig) -> Vec<LineKind> {
+    src.lines()
+        .enumerate()
+        .map(|(num, line)| {
+            let rest = line.trim_start_matches([' ', '\t']);
+            match rest {
+                "" => LineKind::Blank,
+                _ if rest.starts_with(". ") => LineKind::CommentOnly,
+                _ if config.comment_marker.map_or(false, |m| rest.starts_with(m)) => {
+                    LineKind::CommentOnly
+                }
+                _ if num == 0 && rest.starts_with("#!") => LineKind::CommentOnly,
+                _ => LineKind::Code,
+            }
+        })
+        .collect()
+}
 
 pub fn parse(file: &File) -> Result<ast::File, Vec<Error>> {
-    let lines = lines::parse(file.code())?;
+    parse_with_config(file, Default::default())
+}
+
+pub fn parse_with_config(file: &File, config: ParseConfig) -> Result<ast::File<'_>, Vec<Error>> {
+    parse_with_warnings(file, config).map(|(file, _)| file)
+}
+
+/// Like `parse`, but hands back accumulated warnings too -
+///     diagnostics that flag something questionable without
+///     failing the parse.
+pub fn parse_with_warnings(
+    file: &File,
+    config: ParseConfig,
+) -> Result<(ast::File<'_>, Vec<Error>), Vec<Error>> {
+    let (lines, warnings) = lines::parse(file.code(), &config)?;
     let file_span = file.span();
-    match tree::parse_line_hierarchy(&mut lines.into_iter().peekable(), 0) {
-        Ok(v) => Ok(ast::File::new(file, v, file_span)),
+    let unit = config.indent_width as usize;
+    match tree::parse_line_hierarchy(&mut lines.into_iter().peekable(), 0, unit) {
+        Ok(v) => Ok((ast::File::new(file, v, file_span), warnings)),
         Err(e) => Err(vec![e]),
     }
 }
+
+/// Owning counterpart of `ast::File` for sources not backed
+///     by a file on disk - keeps the read `File` alongside the tree.
+#[derive(getset::Getters)]
+pub struct Parsed {
+    #[getset(get = "pub")]
+    file: File,
+    #[getset(get = "pub")]
+    roots: Vec<ast::Line>,
+    #[getset(get = "pub")]
+    span: Span,
+}
+
+impl Parsed {
+    /// Depth-first walk over every line - see `ast::File::lines`.
+    pub fn lines(&self) -> ast::Lines<'_> {
+        ast::Lines::new(&self.roots)
+    }
+
+    /// Stable dump for snapshot tests - see `ast::debug_tree`.
+    pub fn debug_tree(&self) -> String {
+        ast::debug_roots(&self.roots)
+    }
+
+    /// Fold regions from indentation -
+    ///     see `ast::File::folding_ranges`.
+    pub fn folding_ranges(&self) -> Vec<ast::FoldRange> {
+        ast::folding_ranges(&self.roots, &self.file)
+    }
+
+    /// Per-parse metrics - see `ast::File::stats`.
+    pub fn stats(&self) -> ast::AstStats {
+        ast::stats(&self.roots)
+    }
+
+    /// Kind of every physical line - see the free `line_kinds`.
+    ///     The default config, matching how `self` was parsed.
+    pub fn line_kinds(&self) -> Vec<LineKind> {
+        line_kinds(self.file.code(), &ParseConfig::default())
+    }
+
+    /// Root-to-innermost node path covering `pos` -
+    ///     see `ast::File::node_at`.
+    pub fn node_at(&self, pos: Position) -> Option<Vec<ast::PathNode<'_>>> {
+        ast::node_at(&self.roots, pos)
+    }
+
+    /// Reparses a single edited line (0-based) and splices it back,
+    ///     shifting later spans by the length difference.
+    /// Falls back to a full reparse when the edit changes the
+    ///     indentation offset - that restructures the hierarchy.
+    pub fn reparse_line(&mut self, line_num: usize, new_text: &str) -> Result<(), Vec<Error>> {
+        let config = ParseConfig::default();
+        let old_text = match self.file.code().split('\n').nth(line_num) {
+            Some(text) => text.to_string(),
+            None => {
+                let reason = format!("no line {}", line_num);
+                return Err(vec![Box::new(ReadFailed::new(Default::default(), reason))]);
+            }
+        };
+        let file = match self.file.with_line_replaced(line_num, new_text) {
+            Ok(file) => file,
+            Err(reason) => return Err(vec![Box::new(ReadFailed::new(Default::default(), reason))]),
+        };
+        let (new_line, _) = lines::parse(&format!("{}\n", new_text), &config)?;
+        let old_line = match lines::parse(&format!("{}\n", old_text), &config) {
+            Ok((old_line, _)) => old_line,
+            Err(_) => return self.reparse_all(file),
+        };
+        match (&old_line[..], &new_line[..]) {
+            ([], []) => {}
+            ([(old_offset, _)], [(new_offset, _)]) if old_offset == new_offset => {}
+            _ => return self.reparse_all(file),
+        }
+        let line_begin: usize = self
+            .file
+            .code()
+            .split('\n')
+            .take(line_num)
+            .map(|line| line.chars().count() + 1)
+            .sum();
+        let old_len = old_text.chars().count();
+        let delta = new_text.chars().count() as isize - old_len as isize;
+        for root in &mut self.roots {
+            root.shift_positions(line_begin + old_len + 1, delta)
+        }
+        if let Some((_, mut line)) = new_line.into_iter().next() {
+            line.shift_positions(0, line_begin as isize);
+            match find_line_mut(&mut self.roots, line_begin, line_begin + old_len) {
+                Some(target) => target.sent = line.sent,
+                None => return self.reparse_all(file),
+            }
+        }
+        for root in &mut self.roots {
+            root.refresh_span()
+        }
+        self.span = file.span();
+        self.file = file;
+        Ok(())
+    }
+
+    fn reparse_all(&mut self, file: File) -> Result<(), Vec<Error>> {
+        let config = ParseConfig::default();
+        let (lines, _) = lines::parse(file.code(), &config)?;
+        let span = file.span();
+        let unit = config.indent_width as usize;
+        match tree::parse_line_hierarchy(&mut lines.into_iter().peekable(), 0, unit) {
+            Ok(roots) => {
+                *self = Parsed { file, roots, span };
+                Ok(())
+            }
+            Err(e) => Err(vec![e]),
+        }
+    }
+}
+
+// The line whose sentence begins within `[begin, end]`.
+fn find_line_mut(lines: &mut [ast::Line], begin: usize, end: usize) -> Option<&mut ast::Line> {
+    for line in lines {
+        let b = line.sent.span.begin().as_usize();
+        if b >= begin && b <= end {
+            return Some(line);
+        }
+        if let Some(found) = find_line_mut(&mut line.extension, begin, end) {
+            return Some(found);
+        }
+        if let Some(found) = find_line_mut(&mut line.block, begin, end) {
+            return Some(found);
+        }
+    }
+    None
+}
+
+/// Reads all of `reader` (UTF-8 only) and parses it.
+pub fn parse_reader<R: std::io::Read>(reader: R) -> Result<Parsed, Vec<Error>> {
+    parse_owned(File::new_reader(reader))
+}
+
+/// Parses an in-memory string without any `File` boilerplate.
+pub fn parse_str(src: &str) -> Result<Parsed, Vec<Error>> {
+    parse_str_named("", src)
+}
+
+/// `let parsed: Parsed = src.parse()?` - `parse_str` under the
+///     standard trait.
+impl std::str::FromStr for Parsed {
+    type Err = Vec<Error>;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        parse_str(src)
+    }
+}
+
+/// Same, with a file name for diagnostics.
+pub fn parse_str_named(name: &str, src: &str) -> Result<Parsed, Vec<Error>> {
+    parse_owned(File::new_str(name.into(), src))
+}
+
+/// Parses `file` one line at a time without building the whole
+///     tree - for tooling that folds or filters lazily. The
+///     hierarchy pass never runs: sub lines come out flat with
+///     their indentation offsets, in document order. Lines joined
+///     by `\` or an open bracket are accumulated until complete.
+pub fn parse_lines<'a>(file: &'a File, config: &'a ParseConfig) -> ParseLines<'a> {
+    lines::ParseLines::new(file.code(), config)
+}
+
+/// Outcome of `parse_incomplete`.
+pub enum ParseStatus {
+    Complete(Parsed),
+    /// The source looks like a prefix of something valid - a REPL
+    ///     should read more lines before reporting anything.
+    NeedMore,
+    Error(Vec<Error>),
+}
+
+/// REPL-friendly parse: errors that more input could fix - an
+///     unterminated string, a bracket still open at the end, a
+///     trailing `\` continuation - come back as `NeedMore` instead
+///     of `Error`. A missing final newline is supplied, so prompt
+///     input can be fed as typed.
+/// An open indentation block can't be told apart from a complete
+///     one - any line may grow a block - so a REPL conventionally
+///     closes blocks on an empty input line instead.
+pub fn parse_incomplete(src: &str) -> ParseStatus {
+    let owned;
+    let src = match src.ends_with('\n') {
+        true => src,
+        false => {
+            owned = format!("{}\n", src);
+            owned.as_str()
+        }
+    };
+    match parse_str(src) {
+        Ok(parsed) => ParseStatus::Complete(parsed),
+        Err(errors) => match lines::recoverable_at_eof(&errors) {
+            true => ParseStatus::NeedMore,
+            false => ParseStatus::Error(errors),
+        },
+    }
+}
+
+fn parse_owned(file: Result<File, String>) -> Result<Parsed, Vec<Error>> {
+    let file = match file {
+        Ok(file) => file,
+        Err(reason) => return Err(vec![Box::new(ReadFailed::new(Default::default(), reason))]),
+    };
+    let config = ParseConfig::default();
+    let (lines, _) = lines::parse(file.code(), &config)?;
+    let span = file.span();
+    let unit = config.indent_width as usize;
+    match tree::parse_line_hierarchy(&mut lines.into_iter().peekable(), 0, unit) {
+        Ok(roots) => Ok(Parsed { file, roots, span }),
+        Err(e) => Err(vec![e]),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::common::error::ErrorKind;
+
+    #[test]
+    fn str_convenience() {
+        let parsed = parse_str("f x\n").unwrap();
+        assert_eq!(parsed.roots().len(), 1);
+        let named = parse_str_named("snippet.yapl", "f x\n").unwrap();
+        assert_eq!(named.file().get_path().to_str(), Some("snippet.yapl"));
+    }
+
+    #[test]
+    fn incomplete_input() {
+        // Prompt input arrives without a final newline.
+        match parse_incomplete("f x") {
+            ParseStatus::Complete(parsed) => assert_eq!(parsed.roots().len(), 1),
+            _ => panic!("complete line not recognized"),
+        }
+        // More input could still fix these.
+        for src in ["f (a, b", "f \"unterminated", "f x \\"] {
+            assert!(
+                matches!(parse_incomplete(src), ParseStatus::NeedMore),
+                "{:?}",
+                src
+            )
+        }
+        // No amount of further lines closes a bracket never opened.
+        assert!(matches!(parse_incomplete("f x)"), ParseStatus::Error(_)));
+    }
+
+    #[test]
+    fn shebang() {
+        let source = "f x\ng (1, 2)\n";
+        let shebang = "#!/usr/bin/env yapl\n";
+        let mut plain = parse_str(source).unwrap();
+        let with = parse_str(&format!("{}{}", shebang, source)).unwrap();
+        // The body parses to the same tree, only shifted past the
+        //     shebang line.
+        for root in &mut plain.roots {
+            root.shift_positions(0, shebang.chars().count() as isize)
+        }
+        assert_eq!(plain.roots, with.roots);
+        // Only the first line is eligible: a later `#!` is two
+        //     ordinary special tokens.
+        let parsed = parse_str("f x\n#! y\n").unwrap();
+        assert_eq!(parsed.roots()[1].sent.sent.len(), 3);
+    }
+
+    #[test]
+    fn line_indent() {
+        let parsed = parse_str("f x\n  g y\n    h z\n").unwrap();
+        let root = &parsed.roots()[0];
+        assert_eq!(root.indent(), 0);
+        assert_eq!(root.block[0].indent(), 1);
+        assert_eq!(root.block[0].block[0].indent(), 2);
+    }
+
+    #[test]
+    fn streaming_lines() {
+        let config = ParseConfig::default();
+        let file = File::new_str("".into(), "f x\n  g (1,\n     2)\nh y\n").unwrap();
+        let lines: Vec<_> = parse_lines(&file, &config).map(Result::unwrap).collect();
+        let offsets: Vec<_> = lines.iter().map(|(offset, _)| *offset).collect();
+        assert_eq!(offsets, [0, 1, 0]);
+        // Spans are whole-file, as if parsed in one go.
+        assert_eq!(lines[2].1.span.begin().as_usize(), "f x\n  g (1,\n     2)\n".chars().count());
+        // An error line is yielded in place, later lines still come.
+        let file = File::new_str("".into(), "f x\ng \u{1}\nh y\n").unwrap();
+        let results: Vec<_> = parse_lines(&file, &config).collect();
+        assert_eq!(results.len(), 3);
+        assert!(results[0].is_ok() && results[1].is_err() && results[2].is_ok());
+    }
+
+    #[test]
+    fn structural_equality() {
+        let a = parse_str("f (x, 1)\n").unwrap();
+        let b = parse_str("\nf (x, 1)\n").unwrap();
+        // Spans differ, the structure doesn't.
+        assert!(a.roots()[0] != b.roots()[0]);
+        assert!(a.roots()[0].structural_eq(&b.roots()[0]));
+        let c = parse_str("f (x, 2)\n").unwrap();
+        assert!(!a.roots()[0].structural_eq(&c.roots()[0]));
+    }
+
+    // Transformations work on a clone, the original stays intact.
+    #[test]
+    fn clone_for_transforms() {
+        let parsed = parse_str("f (x, 1)\n").unwrap();
+        let mut copy = parsed.roots().clone();
+        copy[0].sent.sent.truncate(1);
+        assert_eq!(parsed.roots()[0].sent.sent.len(), 2);
+        assert_eq!(copy[0].sent.sent.len(), 1);
+    }
+
+    #[test]
+    fn folding_ranges() {
+        let fold = |src: &str| -> Vec<(usize, usize)> {
+            parse_str(src)
+                .unwrap()
+                .folding_ranges()
+                .iter()
+                .map(|range| (range.begin, range.end))
+                .collect()
+        };
+        // Nested blocks nest their ranges, parents first.
+        assert_eq!(fold("a\n  b\n    c\nd\n  e\n"), [(0, 2), (1, 2), (3, 4)]);
+        // A line without sub lines folds nothing.
+        assert_eq!(fold("a\nb\n"), []);
+        // Blank and comment lines inside a block fold with it.
+        assert_eq!(fold("a\n  b\n\n. note\n  c\n"), [(0, 4)]);
+    }
+
+    // The hierarchy pass rejects indentation that skips a level
+    //     or lands between the levels actually opened.
+    #[test]
+    fn indent_jump_errors() {
+        for src in ["f x\n    g y\n", "f x\n g y\n"] {
+            match parse_str(src) {
+                Err(errors) => assert_eq!(errors[0].kind(), ErrorKind::WrongLineOffset),
+                Ok(_) => panic!("{:?} parsed", src),
+            }
+        }
+        // Dedent back to an opened level is fine.
+        assert!(parse_str("f x\n  g y\nh z\n").is_ok());
+    }
+
+    // Blank, whitespace-only and comment-only lines are dropped
+    //     before the hierarchy pass - they never close a block.
+    #[test]
+    fn blank_lines_keep_blocks() {
+        for src in [
+            "f x\n  g y\n\n  h z\n",
+            "f x\n  g y\n   \n  h z\n",
+            "f x\n  g y\n. note\n  h z\n",
+        ] {
+            let parsed = parse_str(src).unwrap();
+            assert_eq!(parsed.roots().len(), 1, "{:?}", src);
+            assert_eq!(parsed.roots()[0].block.len(), 2, "{:?}", src);
+        }
+    }
+
+    // Dedenting to a level that was never opened: the extension
+    //     sits at offset 3, so offset 2 matches nothing.
+    #[test]
+    fn dedent_mismatch() {
+        let errors = match parse_str("f x\n      g y\n    h\n") {
+            Err(errors) => errors,
+            Ok(_) => panic!("mismatched dedent parsed"),
+        };
+        assert_eq!(errors[0].kind(), ErrorKind::DedentMismatch);
+        // The span covers the leading whitespace of the line.
+        let span = errors[0].span();
+        assert_eq!(span.begin().as_usize(), "f x\n      g y\n".chars().count());
+        assert_eq!(span.end().as_usize(), span.begin().as_usize() + 4);
+    }
+
+    #[test]
+    fn node_at_innermost() {
+        let parsed = parse_str("f (a.b, 1)\n").unwrap();
+        let pos = Position::new(5).unwrap(); // The `b` of `a.b`.
+        let path = parsed.node_at(pos).unwrap();
+        assert!(matches!(path[0], ast::PathNode::Line(_)));
+        let last = path.last().unwrap();
+        match last {
+            ast::PathNode::Expr(expr) => {
+                assert!(matches!(expr.expr, ast::ExprT::Chain(_)));
+                assert!(expr.span.contains_position(pos));
+            }
+            other => panic!("not an expression: {:?}", other),
+        }
+        // The path is ordered outside-in.
+        for pair in path.windows(2) {
+            assert!(pair[0].span().contains(pair[1].span()));
+        }
+        assert!(parsed.node_at(Position::new(11).unwrap()).is_none());
+    }
+
+    #[test]
+    fn lines_walk() {
+        let parsed = parse_str("f x\n  g y\n    h\n  k\nm\n").unwrap();
+        let depths: Vec<_> = parsed
+            .lines()
+            .map(|(depth, line)| (depth, line.sent.span.begin().as_usize()))
+            .collect();
+        assert_eq!(depths, [(0, 0), (1, 6), (2, 14), (1, 18), (0, 20)]);
+    }
+
+    // Files saved by Windows editors may open with a UTF-8 BOM -
+    //     it's stripped before line splitting ever sees it.
+    #[test]
+    fn bom_stripped() {
+        let plain = parse_str("f x\n  g y\n").unwrap();
+        let bom = parse_str("\u{feff}f x\n  g y\n").unwrap();
+        assert_eq!(plain.roots(), bom.roots());
+    }
+
+    #[test]
+    fn reader_crlf() {
+        let parsed = parse_reader("f x\r\n  g y\r\n".as_bytes()).unwrap();
+        assert_eq!(parsed.file().code(), "f x\n  g y\n");
+        assert_eq!(parsed.roots().len(), 1);
+    }
+
+    #[test]
+    fn tokenize_classification() {
+        let tokens: Vec<_> = tokenize("let x = 42\n").collect();
+        assert!(matches!(tokens[0].0, Token::Word(_)));
+        assert!(matches!(tokens[4].0, Token::Special(_)));
+        assert!(matches!(tokens[6].0, Token::LitInt(42, _, _)));
+        assert!(matches!(tokens[7].0, Token::NewLine));
+    }
+
+    // CRLF and bare-CR files produce the same tree and spans
+    //     as their LF version.
+    #[test]
+    fn line_ending_independence() {
+        let lf = parse_reader("f x\n  g y\n".as_bytes()).unwrap();
+        let crlf = parse_reader("f x\r\n  g y\r\n".as_bytes()).unwrap();
+        let cr = parse_reader("f x\r  g y\r".as_bytes()).unwrap();
+        assert_eq!(crlf.roots(), lf.roots());
+        assert_eq!(cr.roots(), lf.roots());
+        assert_eq!(crlf.span(), lf.span());
+    }
+
+    #[test]
+    fn reparse_single_line() {
+        let mut parsed = parse_reader("f x\n  g y\n  h 12\n".as_bytes()).unwrap();
+        parsed.reparse_line(1, "  g longer").unwrap();
+        assert_eq!(parsed.file().code(), "f x\n  g longer\n  h 12\n");
+        // The sibling after the edit keeps pointing at its source text.
+        let h = &parsed.roots()[0].block[1];
+        let expected = "f x\n  g longer\n  ".chars().count();
+        assert_eq!(h.sent.span.begin().as_usize(), expected);
+    }
+
+    #[test]
+    fn reparse_offset_change_falls_back() {
+        let mut parsed = parse_reader("f x\n  g y\n".as_bytes()).unwrap();
+        assert_eq!(parsed.roots().len(), 1);
+        parsed.reparse_line(1, "g y").unwrap();
+        assert_eq!(parsed.roots().len(), 2);
+    }
+
+    #[test]
+    fn parse_stats() {
+        let parsed = parse_str("f 1 \"s\"\n  g (2, x)\nh 3.5\n").unwrap();
+        let stats = parsed.stats();
+        assert_eq!(stats.lines, 3);
+        // Three line sentences plus two bracket parts.
+        assert_eq!(stats.sentences, 5);
+        assert_eq!(stats.chains, 4);
+        assert_eq!(stats.ints, 2);
+        assert_eq!(stats.floats, 1);
+        assert_eq!(stats.strings, 1);
+        assert_eq!(stats.brackets, 1);
+        // `g` is one level down, its bracket one more.
+        assert_eq!(stats.max_depth, 3);
+        assert_eq!(parse_str("").unwrap().stats(), ast::AstStats::default());
+    }
+
+    #[test]
+    fn line_kind_classification() {
+        use LineKind::*;
+        let config = ParseConfig::default();
+        let kinds = line_kinds("f x\n\n. note\n   \n  g y\n", &config);
+        assert_eq!(kinds, [Code, Blank, CommentOnly, Blank, Code]);
+        // A doc comment is a statement in the tree, not trivia.
+        assert_eq!(line_kinds(".. doc\n", &config), [Code]);
+        // A configured marker classifies like `. ` does; without
+        //     the config `#` is an ordinary special token.
+        let hash = ParseConfig {
+            comment_marker: Some("#"),
+            ..config
+        };
+        let kinds = line_kinds("# note\nf x # trailing\n", &hash);
+        assert_eq!(kinds, [CommentOnly, Code]);
+        assert_eq!(line_kinds("# note\n", &config), [Code]);
+        // The shebang parses to nothing - first line only.
+        let kinds = line_kinds("#!/usr/bin/env yapl\nf x\n", &config);
+        assert_eq!(kinds, [CommentOnly, Code]);
+        assert_eq!(parse_str("f x\n\n").unwrap().line_kinds(), [Code, Blank]);
+    }
+
+    #[test]
+    fn indent_detection() {
+        assert_eq!(detect_indent("f\n  g\n    h\n"), Some(2));
+        assert_eq!(detect_indent("f\n    g\n        h\n"), Some(4));
+        assert_eq!(detect_indent("f\n        g\n"), Some(8));
+        // Inconsistent indentation fits no unit.
+        assert_eq!(detect_indent("f\n   g\n"), None);
+        assert_eq!(detect_indent("f\n\tg\n"), None);
+        // Nothing indented - nothing to detect.
+        assert_eq!(detect_indent("f\ng\n"), None);
+        // Blank lines don't count as zero indents.
+        assert_eq!(detect_indent("f\n\n  g\n"), Some(2));
+    }
+
+    #[test]
+    fn reader_invalid_utf8() {
+        match parse_reader(&[0x66, 0xff, 0xfe][..]) {
+            Err(errors) => assert_eq!(errors.len(), 1),
+            Ok(_) => panic!("invalid UTF-8 should be rejected"),
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use super::ast::{Expr, ExprT, File, Line, Sent};
+use super::symbol::{BracketType, Radix};
+use super::ParseConfig;
+
+/// Emits canonically formatted source for a parsed file.
+/// Reparsing the output yields an equivalent tree (up to spans).
+pub fn print(file: &File, config: &ParseConfig) -> String {
+    print_lines(file.roots(), config)
+}
+
+pub fn print_lines(lines: &[Line], config: &ParseConfig) -> String {
+    let mut out = String::new();
+    for line in lines {
+        print_line(&mut out, line, 0, config)
+    }
+    out
+}
+
+fn print_line(out: &mut String, line: &Line, offset: usize, config: &ParseConfig) {
+    out.extend(std::iter::repeat(' ').take(offset * config.indent_width as usize));
+    print_sent(out, &line.sent);
+    out.push('\n');
+    // Offsets mirror `tree::parse_line_hierarchy`.
+    for extension in &line.extension {
+        print_line(out, extension, offset + 3, config)
+    }
+    for block in &line.block {
+        print_line(out, block, offset + 1, config)
+    }
+}
+
+fn print_sent(out: &mut String, sent: &Sent) {
+    for (i, expr) in sent.sent.iter().enumerate() {
+        if i > 0 {
+            out.push(' ')
+        }
+        print_expr(out, expr)
+    }
+}
+
+fn print_expr(out: &mut String, expr: &Expr) {
+    match &expr.expr {
+        ExprT::Inner(inner) => {
+            out.push('.');
+            print_expr(out, inner)
+        }
+        ExprT::Special(s) => out.push_str(&s.to_string()),
+        ExprT::Keyword(k) => out.push_str(&k.to_string()),
+        ExprT::Chain(chain) => {
+            let chain: Vec<_> = chain.iter().map(|s| s.to_string()).collect();
+            out.push_str(&chain.join("."))
+        }
+        ExprT::Bracket(bt, parts) => {
+            let (open, close) = match bt {
+                BracketType::Round => ('(', ')'),
+                BracketType::Square => ('[', ']'),
+                BracketType::Curly => ('{', '}'),
+            };
+            out.push(open);
+            for (i, part) in parts.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ")
+                }
+                print_sent(out, part)
+            }
+            out.push(close)
+        }
+        ExprT::LitStr(s) => out.push_str(&format!("{:?}", s)),
+        ExprT::LitInt(i, radix, suffix) => {
+            // Sign and magnitude: `{:x}` of a negative value would
+            //     emit its two's complement, which doesn't reparse.
+            //     `unsigned_abs` survives `i64::MIN` as well.
+            let sign = if *i < 0 { "-" } else { "" };
+            let magnitude = i.unsigned_abs();
+            match radix {
+                Radix::Binary => out.push_str(&format!("{}0b{:b}", sign, magnitude)),
+                Radix::Octal => out.push_str(&format!("{}0o{:o}", sign, magnitude)),
+                Radix::Decimal => out.push_str(&i.to_string()),
+                Radix::Hexadecimal => out.push_str(&format!("{}0x{:x}", sign, magnitude)),
+            }
+            if let Some(s) = suffix {
+                out.push_str(&s.to_string())
+            }
+        }
+        ExprT::LitFloat(f, suffix) => {
+            match f.fract() == 0.0 {
+                true => out.push_str(&format!("{:.1}", f)),
+                false => out.push_str(&f.to_string()),
+            }
+            if let Some(s) = suffix {
+                out.push_str(&s.to_string())
+            }
+        }
+        ExprT::LitChar(c) => out.push_str(&format!("{:?}", c)),
+        ExprT::LitBool(b) => out.push_str(&b.to_string()),
+        ExprT::DocComment(text) => {
+            out.push_str(".. ");
+            out.push_str(text)
+        }
+        ExprT::Comment(text) => {
+            out.push_str(". ");
+            out.push_str(text)
+        }
+        // Error placeholders have no surface form, keep a comment.
+        ExprT::Error => out.push_str(". error"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{lines, tree};
+    use super::*;
+
+    fn roots(code: &str, config: &ParseConfig) -> Vec<Line> {
+        let (lines, _) = lines::parse(code, config).unwrap();
+        tree::parse_line_hierarchy(&mut lines.into_iter().peekable(), 0, 2).unwrap()
+    }
+
+    #[test]
+    fn print_is_stable() {
+        let code = "let a 3 (b, c.d [1 + 2])\n  nested \"s\\n\"\n";
+        let config = Default::default();
+        let original = roots(code, &config);
+        let printed = print_lines(&original, &config);
+        // Reparsing the output yields an equivalent tree - mere
+        //     idempotence would also hold for dropped tokens.
+        let reparsed = roots(&printed, &config);
+        assert_eq!(original.len(), reparsed.len());
+        assert!(original
+            .iter()
+            .zip(&reparsed)
+            .all(|(a, b)| a.structural_eq(b)));
+        assert_eq!(printed, print_lines(&reparsed, &config));
+    }
+
+    // Negative non-decimal literals print as sign and magnitude -
+    //     two's-complement digits would overflow the reparse.
+    #[test]
+    fn negative_radix_literals() {
+        let config = Default::default();
+        for code in ["-0xff\n", "-0b101\n", "-0o17\n", "-5\n", "0xffu8\n"] {
+            let original = roots(code, &config);
+            let printed = print_lines(&original, &config);
+            assert_eq!(printed, code);
+            let reparsed = roots(&printed, &config);
+            assert!(original
+                .iter()
+                .zip(&reparsed)
+                .all(|(a, b)| a.structural_eq(b)));
+        }
+    }
+}
@@ -1,5 +1,8 @@
 /// Basic grammatical rules.
 /// This is, if it will be possible, will be simplified.
+/// `SymbolType::from(char)` is the exact classification the lexer
+///     starts from - one char to one kind, no context - so external
+///     tooling can classify characters the same way the parser does.
 #[derive(Debug, PartialEq, Clone, Copy, serde::Serialize, serde::Deserialize)]
 pub enum SymbolType {
     /// End of stream.
@@ -14,6 +17,9 @@ pub enum SymbolType {
     /// "\"".
     /// Defines `string`.
     Quote,
+    /// "'".
+    /// Defines `char` literal.
+    SingleQuote,
     /// is_alphabetic or "_".
     /// Part of `name` or `literal`.
     Letter(char),
@@ -24,15 +30,23 @@ pub enum SymbolType {
     /// Described alone as it'c basis of language.
     /// Type, is_open.
     Bracket(BracketType, bool),
-    /// "<> +-*/= &|^! '".
+    /// "<> +-*/= &|^! #;@".
     /// Unites in `special`.
     Special(char),
-    /// " \t".
+    /// " ".
     /// Defines indent and separates tokens.
     Whitespace(usize),
+    /// "\t".
+    /// Kept separate from `Whitespace`: tab indentation is configurable
+    ///     and mixing tabs with spaces should be detectable.
+    Tab,
     /// "\n".
     /// Separates lines.
     NewLine,
+    /// "\\".
+    /// Joins physical lines when it closes one; forbidden
+    ///     anywhere else (except `string`).
+    Backslash,
     /// Any other unicode.
     /// Forbidden to be used (except `string`).
     Other(char),
@@ -45,6 +59,46 @@ pub enum BracketType {
     Curly,
 }
 
+impl BracketType {
+    pub fn open_char(&self) -> char {
+        match self {
+            Self::Round => '(',
+            Self::Square => '[',
+            Self::Curly => '{',
+        }
+    }
+
+    pub fn close_char(&self) -> char {
+        match self {
+            Self::Round => ')',
+            Self::Square => ']',
+            Self::Curly => '}',
+        }
+    }
+}
+
+/// Radix of an integer literal as written in source:
+/// "0b", "0o", "0x" prefix or no prefix at all.
+#[derive(Debug, PartialEq, Clone, Copy, serde::Deserialize, serde::Serialize)]
+pub enum Radix {
+    Binary,
+    Octal,
+    Decimal,
+    Hexadecimal,
+}
+
+impl Radix {
+    pub fn base(&self) -> u32 {
+        match self {
+            Self::Binary => 2,
+            Self::Octal => 8,
+            Self::Decimal => 10,
+            Self::Hexadecimal => 16,
+        }
+    }
+}
+
+/// `None` - the end of the stream - classifies as `EOS`.
 impl From<Option<char>> for SymbolType {
     fn from(symbol: Option<char>) -> Self {
         symbol.map(|c| c.into()).unwrap_or(Self::EOS)
@@ -57,9 +111,10 @@ impl From<char> for SymbolType {
             '.' => Self::Dot,
             ',' => Self::Comma,
             '"' => Self::Quote,
+            '\'' => Self::SingleQuote,
             c if c.is_alphabetic() || c == '_' => Self::Letter(c),
             c if c.is_ascii_digit() => Self::Digit(c),
-            c if "<>+-*/=&|^!'#".contains(c) => Self::Special(c),
+            c if "<>+-*/=&|^!#;@".contains(c) => Self::Special(c),
             '(' => Self::Bracket(BracketType::Round, true),
             '[' => Self::Bracket(BracketType::Square, true),
             '{' => Self::Bracket(BracketType::Curly, true),
@@ -67,17 +122,50 @@ impl From<char> for SymbolType {
             ']' => Self::Bracket(BracketType::Square, false),
             '}' => Self::Bracket(BracketType::Curly, false),
             ' ' => Self::Whitespace(1),
-            '\t' => Self::Whitespace(TAB_TO_SPACES),
+            '\t' => Self::Tab,
             '\n' => Self::NewLine,
+            '\\' => Self::Backslash,
             c => Self::Other(c),
         }
     }
 }
 
-const TAB_TO_SPACES: usize = 2;
-pub fn offset(offset_in_spaces: usize) -> Option<usize> {
+/// Multi-character operators recognized as single `special` tokens.
+/// The longest match wins; sequences outside the table fall back
+///     to single-char tokens. Reused by the AST layer.
+pub const OPERATORS: &[&str] = &[
+    "<<=", ">>=", "<<", ">>", "==", "!=", "<=", ">=", "<-", "->", "=>", "&&", "||", "+=", "-=",
+    "*=", "/=",
+];
+
+/// Whether `current` extended by `next` can still begin an operator.
+pub fn operator_prefix(current: &str, next: char) -> bool {
+    OPERATORS.iter().any(|op| {
+        op.len() > current.len() && op.starts_with(current) && op[current.len()..].starts_with(next)
+    })
+}
+
+pub const TAB_TO_SPACES: usize = 2;
+pub fn offset(offset_in_spaces: usize, unit: usize) -> Option<usize> {
     match offset_in_spaces {
-        o if o % TAB_TO_SPACES == 0 => Some(o / TAB_TO_SPACES),
+        o if o % unit == 0 => Some(o / unit),
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn char_classification() {
+        let round = SymbolType::Bracket(BracketType::Round, true);
+        assert_eq!(SymbolType::from('('), round);
+        assert_eq!(SymbolType::from('7'), SymbolType::Digit('7'));
+        assert_eq!(SymbolType::from('+'), SymbolType::Special('+'));
+        assert_eq!(SymbolType::from('@'), SymbolType::Special('@'));
+        assert_eq!(SymbolType::from('\\'), SymbolType::Backslash);
+        assert_eq!(SymbolType::from('é'), SymbolType::Letter('é'));
+        assert_eq!(SymbolType::from(None::<char>), SymbolType::EOS);
+    }
+}
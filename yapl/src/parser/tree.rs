@@ -1,12 +1,27 @@
 use std::iter::Peekable;
 
 use crate::common::error::{raise_error, Result};
-use crate::common::location::Span;
+use crate::common::location::{Position, Span};
 
 use super::ast::Line;
-use super::errors::WrongLineOffset;
+use super::errors::{DedentMismatch, WrongLineOffset};
 
-pub fn parse_line_hierarchy<I>(lines: &mut Peekable<I>, offset: usize) -> Result<Vec<Line>>
+/// `unit` is the indentation width in chars - only used to point
+///     error spans at the leading whitespace.
+pub fn parse_line_hierarchy<I>(lines: &mut Peekable<I>, offset: usize, unit: usize) -> Result<Vec<Line>>
+where
+    I: Iterator<Item = (usize, Line)>,
+{
+    let mut deepest = 0;
+    hierarchy(lines, offset, unit, &mut deepest)
+}
+
+fn hierarchy<I>(
+    lines: &mut Peekable<I>,
+    offset: usize,
+    unit: usize,
+    deepest: &mut usize,
+) -> Result<Vec<Line>>
 where
     I: Iterator<Item = (usize, Line)>,
 {
@@ -15,17 +30,24 @@ where
         match line {
             (of, _) if *of < offset => break,
             (of, _) if *of == offset => {
+                *deepest = offset;
                 let (_, mut line) = lines.next().unwrap();
                 line.update(
-                    parse_line_hierarchy(lines, offset + 3)?,
-                    parse_line_hierarchy(lines, offset + 1)?,
+                    hierarchy(lines, offset + 3, unit, deepest)?,
+                    hierarchy(lines, offset + 1, unit, deepest)?,
                 );
                 result.push(line)
             }
             (of, l) => {
-                let l_s = l.span.begin();
-                let of = *of as usize;
-                raise_error!(WrongLineOffset, Span::new(l_s, l_s.advanced(of)), of)
+                let of = *of;
+                let end = l.span.begin();
+                // To be done: exact width under tab indentation.
+                let begin = Position::new(end.as_usize().saturating_sub(of * unit)).unwrap();
+                let span = Span::new(begin, end);
+                match of < *deepest {
+                    true => raise_error!(DedentMismatch, span, of),
+                    false => raise_error!(WrongLineOffset, span, of),
+                }
             }
         }
     }
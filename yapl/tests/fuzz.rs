@@ -0,0 +1,56 @@
+//! Fuzz-style robustness tests: `parse` must never panic on
+//!     arbitrary input, and every produced `Span` must lie within
+//!     the source bounds.
+
+use proptest::prelude::*;
+
+use yapl::HasSpan;
+
+fn check_line(line: &yapl::parser_ast::Line, chars: usize) {
+    check_span(line.span(), chars);
+    check_span(line.sent.span(), chars);
+    for expr in &line.sent.sent {
+        check_span(expr.span(), chars)
+    }
+    for sub in line.extension.iter().chain(line.block.iter()) {
+        check_line(sub, chars)
+    }
+}
+
+fn check_span(span: yapl::Span, chars: usize) {
+    assert!(span.begin() <= span.end());
+    assert!(span.end().as_usize() <= chars);
+}
+
+proptest! {
+    #[test]
+    fn parse_never_panics(src in "\\PC{0,200}") {
+        let _ = yapl::parse_str(&src);
+    }
+
+    #[test]
+    fn random_indentation(lines in prop::collection::vec((0usize..8, "[a-z (),.]{0,12}"), 0..12)) {
+        let mut src = String::new();
+        for (indent, text) in &lines {
+            src.extend(std::iter::repeat(' ').take(*indent));
+            src.push_str(text);
+            src.push('\n');
+        }
+        let chars = src.chars().count();
+        if let Ok(parsed) = yapl::parse_str(&src) {
+            for line in parsed.roots() {
+                check_line(line, chars)
+            }
+        }
+    }
+
+    #[test]
+    fn error_spans_in_bounds(src in "[a-z0-9 ().,\"'\\\\\n\t]{0,100}") {
+        let chars = src.chars().count();
+        if let Err(errors) = yapl::parse_str(&src) {
+            for error in &errors {
+                check_span(error.span(), chars)
+            }
+        }
+    }
+}
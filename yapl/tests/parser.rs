@@ -18,7 +18,7 @@ fn basics() {
     let result = format!("{:#?}", &parsed.roots());
 
     // This contains more or less all patterns, so remove unit tests.
-    // Since previous version: changed `Line::span`, `LitI`->`LitInt`.
+    // Since previous version: unknown special runs split into operators.
     let expected = std::fs::read_to_string(&out).unwrap();
 
     // `Debug` output is more convenient to read.
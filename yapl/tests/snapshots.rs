@@ -0,0 +1,22 @@
+//! Snapshot tests over `debug_tree`: the dumps under
+//!     `tests/auxiliary/` pin node kinds, literal values and
+//!     spans for a few representative inputs.
+//! To regenerate after an intended change, write the `actual`
+//!     value over the snapshot file and review the diff.
+
+const SNAPSHOTS: &[(&str, &str)] = &[
+    ("snap_calls", "f (a.b, 1)\n  g 0x10\n"),
+    ("snap_literals", "x <- 'c' \"s\" 3.5\n.. doc of x\n"),
+    ("snap_nesting", "a\n  b\n    c\nd [1, 2]\n"),
+];
+
+#[test]
+fn snapshots() {
+    for (name, src) in SNAPSHOTS {
+        let parsed = yapl::parse_str(src).unwrap();
+        let actual = parsed.debug_tree();
+        let path = format!("tests/auxiliary/{}.out", name);
+        let expected = std::fs::read_to_string(&path).unwrap();
+        assert!(actual == expected, "{} differs:\n{}", name, actual);
+    }
+}